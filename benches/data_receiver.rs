@@ -0,0 +1,82 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs LLC <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: Apache-2.0 OR MIT
+ */
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use smtp_proto::request::receiver::DataReceiver;
+
+#[path = "../src/test_support.rs"]
+#[allow(dead_code)]
+mod test_support;
+
+use test_support::PerByteDataReceiver;
+
+/// Builds a dot-stuffed message of about `size` bytes made of 76 character
+/// base64-like lines, with a stuffed dot line every 100 lines.
+fn message(size: usize) -> Vec<u8> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut message = Vec::with_capacity(size + 80);
+    let mut line = 0usize;
+    while message.len() < size {
+        if line % 100 == 99 {
+            message.extend_from_slice(b"..signature\r\n");
+        } else {
+            message.extend((0..76).map(|i| ALPHABET[(line * 7 + i) % ALPHABET.len()]));
+            message.extend_from_slice(b"\r\n");
+        }
+        line += 1;
+    }
+    message.extend_from_slice(b".\r\n");
+    message
+}
+
+fn data_receiver(c: &mut Criterion) {
+    let mut group = c.benchmark_group("data_receiver");
+
+    for size in [64 * 1024, 4 * 1024 * 1024] {
+        let message = message(size);
+        group.throughput(Throughput::Bytes(message.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("memchr", size), &message, |b, message| {
+            let mut buf = Vec::with_capacity(message.len());
+            b.iter(|| {
+                buf.clear();
+                let mut receiver = DataReceiver::new();
+                assert!(receiver.ingest(&mut black_box(message).iter(), &mut buf));
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("statistics", size),
+            &message,
+            |b, message| {
+                let mut buf = Vec::with_capacity(message.len());
+                b.iter(|| {
+                    buf.clear();
+                    let mut receiver = DataReceiver::new().with_statistics(true);
+                    assert!(receiver.ingest(&mut black_box(message).iter(), &mut buf));
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("per_byte", size),
+            &message,
+            |b, message| {
+                let mut buf = Vec::with_capacity(message.len());
+                b.iter(|| {
+                    buf.clear();
+                    let mut receiver = PerByteDataReceiver::default();
+                    assert!(receiver.ingest(&mut black_box(message).iter(), &mut buf));
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, data_receiver);
+criterion_main!(benches);
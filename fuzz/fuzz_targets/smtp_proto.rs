@@ -7,9 +7,14 @@
 #![no_main]
 use libfuzzer_sys::fuzz_target;
 
+#[path = "../../src/test_support.rs"]
+#[allow(dead_code)]
+mod test_support;
+
 use smtp_proto::{
     EhloResponse, Request,
     request::{
+        encoder::DataEncoder,
         parser::Rfc5321Parser,
         receiver::{
             BdatReceiver, DataReceiver, DummyDataReceiver, DummyLineReceiver, LineReceiver,
@@ -54,6 +59,24 @@ fuzz_target!(|data: &[u8]| {
         let _ = Rfc5321Parser::new(&mut bytes.iter()).rcpt_to_parameters(Default::default());
         let _ = Rfc5321Parser::new(&mut bytes.iter()).mechanism();
     }
+
+    // DataEncoder output must be received as the original body, with line
+    // breaks normalized and the final one dropped
+    let split = data.first().map_or(1, |&ch| 1 + ch as usize % 16);
+    let mut encoder = DataEncoder::new();
+    let mut encoded = Vec::new();
+    for chunk in data.chunks(split) {
+        encoder.encode(chunk, &mut encoded).unwrap();
+    }
+    encoder.finish(&mut encoded).unwrap();
+
+    let mut receiver = DataReceiver::new();
+    let mut received = Vec::new();
+    let mut chunks = encoded.chunks(split);
+    assert!(chunks.any(|chunk| receiver.ingest(&mut chunk.iter(), &mut received)));
+    assert!(chunks.next().is_none());
+
+    assert_eq!(received, test_support::normalize_line_breaks(data));
 });
 
 fn into_alphabet(data: &[u8], alphabet: &[u8]) -> Vec<u8> {
@@ -9,9 +9,12 @@
 
 use std::borrow::Cow;
 use std::fmt::Display;
+use std::net::IpAddr;
 
 pub mod request;
 pub mod response;
+#[cfg(test)]
+mod test_support;
 mod tokens;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -41,24 +44,15 @@ pub enum Request<T> {
     Quit,
 }
 
-impl Request<Cow<'_, str>> {
-    pub fn into_owned(self) -> Request<String> {
+impl<T> Request<T> {
+    /// Converts every string value of the request with `f`.
+    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> Request<U> {
         match self {
-            Request::Ehlo { host } => Request::Ehlo {
-                host: host.into_owned(),
-            },
-            Request::Lhlo { host } => Request::Lhlo {
-                host: host.into_owned(),
-            },
-            Request::Helo { host } => Request::Helo {
-                host: host.into_owned(),
-            },
-            Request::Mail { from } => Request::Mail {
-                from: from.into_owned(),
-            },
-            Request::Rcpt { to } => Request::Rcpt {
-                to: to.into_owned(),
-            },
+            Request::Ehlo { host } => Request::Ehlo { host: f(host) },
+            Request::Lhlo { host } => Request::Lhlo { host: f(host) },
+            Request::Helo { host } => Request::Helo { host: f(host) },
+            Request::Mail { from } => Request::Mail { from: from.map(f) },
+            Request::Rcpt { to } => Request::Rcpt { to: to.map(f) },
             Request::Bdat {
                 chunk_size,
                 is_last,
@@ -71,28 +65,18 @@ impl Request<Cow<'_, str>> {
                 initial_response,
             } => Request::Auth {
                 mechanism,
-                initial_response: initial_response.into_owned(),
-            },
-            Request::Noop { value } => Request::Noop {
-                value: value.into_owned(),
-            },
-            Request::Vrfy { value } => Request::Vrfy {
-                value: value.into_owned(),
-            },
-            Request::Expn { value } => Request::Expn {
-                value: value.into_owned(),
-            },
-            Request::Help { value } => Request::Help {
-                value: value.into_owned(),
-            },
-            Request::Etrn { name } => Request::Etrn {
-                name: name.into_owned(),
+                initial_response: f(initial_response),
             },
+            Request::Noop { value } => Request::Noop { value: f(value) },
+            Request::Vrfy { value } => Request::Vrfy { value: f(value) },
+            Request::Expn { value } => Request::Expn { value: f(value) },
+            Request::Help { value } => Request::Help { value: f(value) },
+            Request::Etrn { name } => Request::Etrn { name: f(name) },
             Request::Atrn { domains } => Request::Atrn {
-                domains: domains.into_iter().map(Cow::into_owned).collect(),
+                domains: domains.into_iter().map(f).collect(),
             },
             Request::Burl { uri, is_last } => Request::Burl {
-                uri: uri.into_owned(),
+                uri: f(uri),
                 is_last,
             },
             Request::StartTls => Request::StartTls,
@@ -103,6 +87,12 @@ impl Request<Cow<'_, str>> {
     }
 }
 
+impl<B: ?Sized + ToOwned> Request<Cow<'_, B>> {
+    pub fn into_owned(self) -> Request<B::Owned> {
+        self.map(Cow::into_owned)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(
@@ -110,11 +100,16 @@ impl Request<Cow<'_, str>> {
     derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)
 )]
 pub struct MailFrom<T> {
-    pub address: T,
-    pub flags: u64,
+    pub address: ReversePath<T>,
+    pub route: Vec<T>,
+    pub body: Option<Body>,
+    pub ret: Option<Ret>,
+    pub smtp_utf8: bool,
+    pub require_tls: bool,
+    pub conperm: bool,
     pub size: usize,
     pub trans_id: Option<T>,
-    pub by: i64,
+    pub by: Option<DeliverBy>,
     pub env_id: Option<T>,
     pub solicit: Option<T>,
     pub mtrk: Option<Mtrk<T>>,
@@ -124,18 +119,23 @@ pub struct MailFrom<T> {
     pub mt_priority: i64,
 }
 
-impl MailFrom<Cow<'_, str>> {
-    pub fn into_owned(self) -> MailFrom<String> {
+impl<T> MailFrom<T> {
+    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> MailFrom<U> {
         MailFrom {
-            address: self.address.into_owned(),
-            flags: self.flags,
+            address: self.address.map(&mut f),
+            route: self.route.into_iter().map(&mut f).collect(),
+            body: self.body,
+            ret: self.ret,
+            smtp_utf8: self.smtp_utf8,
+            require_tls: self.require_tls,
+            conperm: self.conperm,
             size: self.size,
-            trans_id: self.trans_id.map(Cow::into_owned),
+            trans_id: self.trans_id.map(&mut f),
             by: self.by,
-            env_id: self.env_id.map(Cow::into_owned),
-            solicit: self.solicit.map(Cow::into_owned),
-            mtrk: self.mtrk.map(Mtrk::into_owned),
-            auth: self.auth.map(Cow::into_owned),
+            env_id: self.env_id.map(&mut f),
+            solicit: self.solicit.map(&mut f),
+            mtrk: self.mtrk.map(|mtrk| mtrk.map(&mut f)),
+            auth: self.auth.map(f),
             hold_for: self.hold_for,
             hold_until: self.hold_until,
             mt_priority: self.mt_priority,
@@ -143,6 +143,12 @@ impl MailFrom<Cow<'_, str>> {
     }
 }
 
+impl<B: ?Sized + ToOwned> MailFrom<Cow<'_, B>> {
+    pub fn into_owned(self) -> MailFrom<B::Owned> {
+        self.map(Cow::into_owned)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(
@@ -150,23 +156,146 @@ impl MailFrom<Cow<'_, str>> {
     derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)
 )]
 pub struct RcptTo<T> {
-    pub address: T,
+    pub address: ForwardPath<T>,
+    pub route: Vec<T>,
     pub orcpt: Option<T>,
-    pub rrvs: i64,
-    pub flags: u64,
+    pub notify: Option<NotifySet>,
+    pub rrvs: Option<Rrvs>,
+    pub conneg: bool,
 }
 
-impl RcptTo<Cow<'_, str>> {
-    pub fn into_owned(self) -> RcptTo<String> {
+impl<T> RcptTo<T> {
+    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> RcptTo<U> {
         RcptTo {
-            address: self.address.into_owned(),
-            orcpt: self.orcpt.map(Cow::into_owned),
+            address: self.address.map(&mut f),
+            route: self.route.into_iter().map(&mut f).collect(),
+            orcpt: self.orcpt.map(f),
+            notify: self.notify,
             rrvs: self.rrvs,
-            flags: self.flags,
+            conneg: self.conneg,
         }
     }
 }
 
+impl<B: ?Sized + ToOwned> RcptTo<Cow<'_, B>> {
+    pub fn into_owned(self) -> RcptTo<B::Owned> {
+        self.map(Cow::into_owned)
+    }
+}
+
+/// Sender of a `MAIL FROM` command. The null reverse-path `<>` is used by bounces.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)
+)]
+pub enum ReversePath<T> {
+    #[default]
+    Null,
+    Mailbox(T),
+}
+
+/// Recipient of a `RCPT TO` command. `<Postmaster>` without a domain refers to
+/// the postmaster of the receiving server (RFC 5321, Section 4.1.1.3).
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)
+)]
+pub enum ForwardPath<T> {
+    #[default]
+    Postmaster,
+    Mailbox(T),
+}
+
+/// Body type declared with the `BODY` parameter (RFC 6152, RFC 3030).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)
+)]
+pub enum Body {
+    SevenBit,
+    EightBitMime,
+    BinaryMime,
+}
+
+/// Part of the message returned in DSNs, requested with the `RET` parameter (RFC 3461).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)
+)]
+pub enum Ret {
+    Full,
+    Hdrs,
+}
+
+/// Delivery deadline requested with the `BY` parameter (RFC 2852).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)
+)]
+pub struct DeliverBy {
+    pub time: i64,
+    pub mode: DeliverByMode,
+    pub trace: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)
+)]
+pub enum DeliverByMode {
+    Notify,
+    Return,
+}
+
+/// Conditions requested with the `NOTIFY` parameter (RFC 3461).
+/// `NOTIFY=NEVER` is represented by an empty set.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)
+)]
+pub struct NotifySet {
+    pub success: bool,
+    pub failure: bool,
+    pub delay: bool,
+}
+
+/// Mailbox ownership timestamp sent with the `RRVS` parameter (RFC 7293).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)
+)]
+pub struct Rrvs {
+    pub time: i64,
+    pub on_fail: RrvsAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)
+)]
+pub enum RrvsAction {
+    Reject,
+    Continue,
+}
+
 pub const MAIL_BODY_7BIT: u64 = 1 << 0;
 pub const MAIL_BODY_8BITMIME: u64 = 1 << 1;
 pub const MAIL_BODY_BINARYMIME: u64 = 1 << 2;
@@ -198,15 +327,21 @@ pub struct Mtrk<T> {
     pub timeout: u64,
 }
 
-impl Mtrk<Cow<'_, str>> {
-    pub fn into_owned(self) -> Mtrk<String> {
+impl<T> Mtrk<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Mtrk<U> {
         Mtrk {
-            certifier: self.certifier.into_owned(),
+            certifier: f(self.certifier),
             timeout: self.timeout,
         }
     }
 }
 
+impl<B: ?Sized + ToOwned> Mtrk<Cow<'_, B>> {
+    pub fn into_owned(self) -> Mtrk<B::Owned> {
+        self.map(Cow::into_owned)
+    }
+}
+
 pub const AUTH_SCRAM_SHA_256_PLUS: u64 = 1u64 << 0;
 pub const AUTH_SCRAM_SHA_256: u64 = 1u64 << 1;
 pub const AUTH_SCRAM_SHA_1_PLUS: u64 = 1u64 << 2;
@@ -300,7 +435,13 @@ pub enum MtPriority {
 )]
 pub struct EhloResponse<T: Display> {
     pub hostname: T,
+    /// Text written after the hostname, `you had me at EHLO` if not set.
+    pub greeting: Option<T>,
     pub capabilities: u32,
+    /// Order in which [`EhloResponse::add_capability`] added the capabilities.
+    /// Capabilities set directly in `capabilities` are written after them, from
+    /// the highest bit to the lowest.
+    pub capability_order: Vec<u32>,
 
     pub auth_mechanisms: u64,
     pub deliver_by: u64,
@@ -319,10 +460,22 @@ pub struct EhloResponse<T: Display> {
 )]
 pub struct Response<T: Display> {
     pub code: u16,
-    pub esc: [u8; 3],
+    pub esc: Option<[u8; 3]>,
     pub message: T,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)
+)]
+pub struct EnhancedStatusCode {
+    pub class: u8,
+    pub subject: u8,
+    pub detail: u8,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(
@@ -355,15 +508,192 @@ pub enum Category {
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Error {
-    NeedsMoreData { bytes_left: usize },
+    NeedsMoreData {
+        bytes_left: usize,
+    },
     UnknownCommand,
-    InvalidSenderAddress,
-    InvalidRecipientAddress,
-    SyntaxError { syntax: &'static str },
-    InvalidParameter { param: &'static str },
-    UnsupportedParameter { param: String },
+    InvalidSenderAddress {
+        context: ErrorContext,
+    },
+    InvalidRecipientAddress {
+        context: ErrorContext,
+    },
+    SyntaxError {
+        syntax: &'static str,
+        context: ErrorContext,
+    },
+    InvalidParameter {
+        param: &'static str,
+        context: ErrorContext,
+    },
+    UnsupportedParameter {
+        param: String,
+        context: ErrorContext,
+    },
+    DuplicateParameter {
+        param: &'static str,
+        context: ErrorContext,
+    },
+    /// Reported by `Request::validate_utf8` (`smtputf8` feature), which runs on
+    /// parsed requests, so only `context.verb` is set.
+    InvalidUtf8 {
+        violation: Utf8Violation,
+        context: ErrorContext,
+    },
     ResponseTooLong,
-    InvalidResponse { code: u16 },
+    InvalidResponse {
+        code: u16,
+    },
+    /// The message is larger than the maximum size of the receiver.
+    MessageTooLarge,
+    /// The message contains a bare CR or LF, see [`BareLfPolicy::Reject`].
+    InvalidLineEnding {
+        ending: BareLineEnding,
+    },
+}
+
+/// Location of the offending token within the command line that failed to parse.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// Command verb, such as `MAIL` or `RCPT`. Empty outside of command parsing.
+    pub verb: &'static str,
+    /// Byte offset of the offending token from the start of the command line.
+    pub offset: usize,
+    /// Length in bytes of the offending token.
+    pub len: usize,
+}
+
+/// Breach of the internationalized email rules of RFC 6531.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Utf8Violation {
+    /// Non-ASCII address or domain without `SMTPUTF8`.
+    EightBitWithoutSmtpUtf8,
+    /// The value is not well-formed UTF-8.
+    MalformedUtf8,
+    /// The value is not in Unicode Normalization Form C.
+    NotNfc,
+    /// A non-ASCII domain label breaks the U-label rules on hyphens or leading
+    /// combining marks, or contains a code point other than a letter, digit,
+    /// combining mark or hyphen. This is only a partial check: the IDNA2008
+    /// tables and the contextual and bidi rules are not applied.
+    InvalidULabel,
+}
+
+/// Line ending other than CRLF found by a `DataReceiver`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BareLineEnding {
+    /// LF not preceded by CR.
+    Lf,
+    /// CR not followed by LF.
+    Cr,
+    /// End of data marked by a `.` line starting or ending with a bare LF,
+    /// only accepted with [`BareLfPolicy::LegacyTerminator`].
+    LfDotLf,
+}
+
+/// Failure to convert a domain between U-labels and A-labels.
+#[cfg(feature = "idna")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdnaError {
+    /// The local part is not ASCII, so the mailbox requires `SMTPUTF8`.
+    NonAsciiLocalPart,
+    /// The label breaks the IDNA2008 rules. `disallowed` is the first code
+    /// point that is never allowed in a domain, if any.
+    InvalidLabel {
+        label: String,
+        disallowed: Option<char>,
+    },
+    /// The converted domain exceeds the DNS length limits.
+    TooLong,
+}
+
+/// `Received:` trace header (RFC 5321, Section 4.4) describing how a message
+/// arrived, see [`Received::write`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)
+)]
+pub struct Received<T> {
+    /// Our hostname.
+    pub hostname: T,
+    /// Greeting command and the domain the client sent with it.
+    pub greeting: Option<(Greeting, T)>,
+    pub peer_ip: Option<IpAddr>,
+    /// Reverse DNS name of the peer.
+    pub peer_hostname: Option<T>,
+    pub tls: bool,
+    pub auth: bool,
+    pub smtp_utf8: bool,
+    pub id: Option<T>,
+    /// Envelope recipient, only to be set for single recipient messages.
+    pub recipient: Option<T>,
+    /// Seconds since the Unix epoch.
+    pub timestamp: i64,
+    /// Offset from UTC of the local time, in minutes.
+    pub utc_offset: i32,
+}
+
+/// Command used by the client to start the session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)
+)]
+pub enum Greeting {
+    Helo,
+    Ehlo,
+    Lhlo,
+}
+
+/// Whether the `EHLO`, `HELO` and `LHLO` domain may contain U-labels.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Utf8EhloPolicy {
+    #[default]
+    Reject,
+    Accept,
+}
+
+/// Content of a message as received by a `DataReceiver`, after removing the
+/// dot-stuffing but before normalizing line endings.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DataStatistics {
+    /// Number of lines, each ending with CRLF or a bare LF.
+    pub lines: usize,
+    /// Length of the longest line in octets, excluding the line ending.
+    pub max_line_len: usize,
+    /// Whether any octet above 127 was found.
+    pub has_8bit: bool,
+    /// Whether any NUL octet was found.
+    pub has_nul: bool,
+    /// Number of CR characters not followed by LF.
+    pub bare_cr: usize,
+    /// Number of LF characters not preceded by CR.
+    pub bare_lf: usize,
+}
+
+/// How a `DataReceiver` handles bare CR and LF characters, which can be used
+/// to smuggle commands past the end of data.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BareLfPolicy {
+    /// Keep them as data, only `CRLF.CRLF` ends the message.
+    #[default]
+    Accept,
+    /// Stop buffering at the first one, consume the rest of the message and
+    /// reject it with [`Error::InvalidLineEnding`]. The bytes before it have
+    /// already been buffered or written and must be discarded by the caller.
+    Reject,
+    /// Replace them with CRLF. Lines following them are not dot-unstuffed, so
+    /// only `CRLF.CRLF` ends the message. A `.` line after a bare line ending,
+    /// such as `a LF . CRLF`, is kept as data and becomes a `CRLF.CRLF` line in
+    /// the body, like any un-stuffed `..` line, so the body must be dot-stuffed
+    /// again, for example with [`DataEncoder`](request::encoder::DataEncoder),
+    /// before it is relayed.
+    Normalize,
+    /// Also treat a bare LF as a line ending, so that `LF.LF` ends the message.
+    LegacyTerminator,
 }
 
 pub(crate) const LF: u8 = b'\n';
@@ -380,11 +710,74 @@ impl IntoString for Vec<u8> {
     }
 }
 
+impl<T> ReversePath<T> {
+    pub fn is_null(&self) -> bool {
+        matches!(self, ReversePath::Null)
+    }
+
+    pub fn mailbox(&self) -> Option<&T> {
+        match self {
+            ReversePath::Mailbox(mailbox) => Some(mailbox),
+            ReversePath::Null => None,
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> ReversePath<U> {
+        match self {
+            ReversePath::Mailbox(mailbox) => ReversePath::Mailbox(f(mailbox)),
+            ReversePath::Null => ReversePath::Null,
+        }
+    }
+}
+
+impl<T> ForwardPath<T> {
+    pub fn is_postmaster(&self) -> bool {
+        matches!(self, ForwardPath::Postmaster)
+    }
+
+    pub fn mailbox(&self) -> Option<&T> {
+        match self {
+            ForwardPath::Mailbox(mailbox) => Some(mailbox),
+            ForwardPath::Postmaster => None,
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> ForwardPath<U> {
+        match self {
+            ForwardPath::Mailbox(mailbox) => ForwardPath::Mailbox(f(mailbox)),
+            ForwardPath::Postmaster => ForwardPath::Postmaster,
+        }
+    }
+}
+
+impl<T: Display> Display for ReversePath<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReversePath::Null => write!(f, "<>"),
+            ReversePath::Mailbox(mailbox) => write!(f, "<{mailbox}>"),
+        }
+    }
+}
+
+impl<T: Display> Display for ForwardPath<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ForwardPath::Postmaster => write!(f, "<Postmaster>"),
+            ForwardPath::Mailbox(mailbox) => write!(f, "<{mailbox}>"),
+        }
+    }
+}
+
 impl<T: Default> Default for MailFrom<T> {
     fn default() -> Self {
         Self {
             address: Default::default(),
-            flags: Default::default(),
+            route: Default::default(),
+            body: Default::default(),
+            ret: Default::default(),
+            smtp_utf8: Default::default(),
+            require_tls: Default::default(),
+            conperm: Default::default(),
             size: Default::default(),
             trans_id: Default::default(),
             by: Default::default(),
@@ -403,9 +796,11 @@ impl<T: Default> Default for RcptTo<T> {
     fn default() -> Self {
         Self {
             address: Default::default(),
+            route: Default::default(),
             orcpt: Default::default(),
+            notify: Default::default(),
             rrvs: Default::default(),
-            flags: Default::default(),
+            conneg: Default::default(),
         }
     }
 }
@@ -423,17 +818,82 @@ impl Display for Error {
                 write!(f, "Needs more data: {} bytes left", bytes_left)
             }
             Error::UnknownCommand => write!(f, "Unknown command"),
-            Error::InvalidSenderAddress => write!(f, "Invalid sender address"),
-            Error::InvalidRecipientAddress => write!(f, "Invalid recipient address"),
-            Error::SyntaxError { syntax } => write!(f, "Syntax error: {}", syntax),
-            Error::InvalidParameter { param } => write!(f, "Invalid parameter: {}", param),
-            Error::UnsupportedParameter { param } => {
+            Error::InvalidSenderAddress { .. } => write!(f, "Invalid sender address"),
+            Error::InvalidRecipientAddress { .. } => write!(f, "Invalid recipient address"),
+            Error::SyntaxError { syntax, .. } => write!(f, "Syntax error: {}", syntax),
+            Error::InvalidParameter { param, .. } => write!(f, "Invalid parameter: {}", param),
+            Error::UnsupportedParameter { param, .. } => {
                 write!(f, "Unsupported parameter: {}", param)
             }
+            Error::DuplicateParameter { param, .. } => {
+                write!(f, "Duplicate parameter: {}", param)
+            }
+            Error::InvalidUtf8 { violation, .. } => write!(f, "{}", violation),
             Error::ResponseTooLong => write!(f, "Response too long"),
             Error::InvalidResponse { code } => write!(f, "Invalid response: {}", code),
+            Error::MessageTooLarge => write!(f, "Message too large"),
+            Error::InvalidLineEnding { ending } => write!(f, "Invalid line ending: {}", ending),
+        }
+    }
+}
+
+impl Error {
+    /// Returns the location of the offending token, if known.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::InvalidSenderAddress { context }
+            | Error::InvalidRecipientAddress { context }
+            | Error::SyntaxError { context, .. }
+            | Error::InvalidParameter { context, .. }
+            | Error::UnsupportedParameter { context, .. }
+            | Error::DuplicateParameter { context, .. }
+            | Error::InvalidUtf8 { context, .. } => Some(context),
+            _ => None,
         }
     }
 }
 
 impl std::error::Error for Error {}
+
+#[cfg(feature = "idna")]
+impl Display for IdnaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdnaError::NonAsciiLocalPart => write!(f, "Non-ASCII local part"),
+            IdnaError::InvalidLabel {
+                label,
+                disallowed: Some(ch),
+            } => write!(
+                f,
+                "Disallowed code point U+{:04X} in label {}",
+                *ch as u32, label
+            ),
+            IdnaError::InvalidLabel { label, .. } => write!(f, "Invalid label {}", label),
+            IdnaError::TooLong => write!(f, "Domain too long"),
+        }
+    }
+}
+
+#[cfg(feature = "idna")]
+impl std::error::Error for IdnaError {}
+
+impl Display for BareLineEnding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BareLineEnding::Lf => write!(f, "Bare LF"),
+            BareLineEnding::Cr => write!(f, "Bare CR"),
+            BareLineEnding::LfDotLf => write!(f, "End of data with bare LF"),
+        }
+    }
+}
+
+impl Display for Utf8Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Utf8Violation::EightBitWithoutSmtpUtf8 => write!(f, "8-bit address without SMTPUTF8"),
+            Utf8Violation::MalformedUtf8 => write!(f, "Malformed UTF-8"),
+            Utf8Violation::NotNfc => write!(f, "Not in Unicode Normalization Form C"),
+            Utf8Violation::InvalidULabel => write!(f, "Invalid U-label"),
+        }
+    }
+}
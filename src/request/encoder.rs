@@ -0,0 +1,254 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs LLC <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: Apache-2.0 OR MIT
+ */
+
+use std::{
+    io::{self, Write},
+    num::NonZeroUsize,
+};
+
+/// Encodes a message body for the `DATA` command: bare CR and LF are replaced
+/// with CRLF, lines starting with `.` are dot-stuffed and [`DataEncoder::finish`]
+/// appends the `CRLF.CRLF` terminator. The body can be split at any byte.
+pub struct DataEncoder {
+    line_start: bool,
+    last_cr: bool,
+}
+
+impl DataEncoder {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            line_start: true,
+            last_cr: false,
+        }
+    }
+
+    pub fn encode(&mut self, bytes: &[u8], mut writer: impl Write) -> io::Result<()> {
+        let mut span_start = 0;
+
+        for (pos, &ch) in bytes.iter().enumerate() {
+            match ch {
+                b'\n' if self.last_cr => {
+                    // CRLF was already written when the CR was seen
+                    span_start = pos + 1;
+                }
+                b'\r' | b'\n' => {
+                    writer.write_all(&bytes[span_start..pos])?;
+                    writer.write_all(b"\r\n")?;
+                    span_start = pos + 1;
+                    self.line_start = true;
+                }
+                b'.' if self.line_start => {
+                    writer.write_all(&bytes[span_start..pos])?;
+                    writer.write_all(b".")?;
+                    span_start = pos;
+                    self.line_start = false;
+                }
+                _ => {
+                    self.line_start = false;
+                }
+            }
+            self.last_cr = ch == b'\r';
+        }
+
+        writer.write_all(&bytes[span_start..])
+    }
+
+    /// Ends the last line, if needed, and writes the terminating `.CRLF`.
+    pub fn finish(self, mut writer: impl Write) -> io::Result<()> {
+        if self.line_start {
+            writer.write_all(b".\r\n")
+        } else {
+            writer.write_all(b"\r\n.\r\n")
+        }
+    }
+}
+
+/// Splits a message body into `BDAT` commands (RFC 3030). Full chunks are
+/// written as soon as more data follows them, so several chunks can be
+/// pipelined, and [`BdatWriter::finish`] sends the rest with `LAST`.
+pub struct BdatWriter {
+    chunk_size: usize,
+    buf: Vec<u8>,
+}
+
+impl BdatWriter {
+    /// Creates a writer that sends `chunk_size` bytes with every `BDAT` command
+    /// but the last one.
+    pub fn new(chunk_size: NonZeroUsize) -> Self {
+        let chunk_size = chunk_size.get();
+        Self {
+            chunk_size,
+            buf: Vec::with_capacity(chunk_size),
+        }
+    }
+
+    /// Adds body data, writing a `BDAT` command for every full chunk. Returns
+    /// the number of commands written, which is the number of replies to expect.
+    pub fn write(&mut self, mut bytes: &[u8], mut writer: impl Write) -> io::Result<usize> {
+        let mut chunks = 0;
+
+        while self.buf.len() + bytes.len() > self.chunk_size {
+            if self.buf.is_empty() {
+                let (chunk, rest) = bytes.split_at(self.chunk_size);
+                write_chunk(chunk, false, &mut writer)?;
+                bytes = rest;
+            } else {
+                let (head, rest) = bytes.split_at(self.chunk_size - self.buf.len());
+                self.buf.extend_from_slice(head);
+                write_chunk(&self.buf, false, &mut writer)?;
+                self.buf.clear();
+                bytes = rest;
+            }
+            chunks += 1;
+        }
+        self.buf.extend_from_slice(bytes);
+
+        Ok(chunks)
+    }
+
+    /// Writes the remaining data as `BDAT n LAST`, or `BDAT 0 LAST` for an
+    /// empty body.
+    pub fn finish(self, writer: impl Write) -> io::Result<()> {
+        write_chunk(&self.buf, true, writer)
+    }
+}
+
+/// Writes a single `BDAT` command followed by its payload.
+pub fn write_chunk(chunk: &[u8], is_last: bool, mut writer: impl Write) -> io::Result<()> {
+    write!(
+        writer,
+        "BDAT {}{}\r\n",
+        chunk.len(),
+        if is_last { " LAST" } else { "" }
+    )?;
+    writer.write_all(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use super::{BdatWriter, DataEncoder};
+    use crate::{
+        Request,
+        request::receiver::{BdatReceiver, DataReceiver},
+        test_support::{normalize_line_breaks, xorshift},
+    };
+
+    #[test]
+    fn data_encoder() {
+        for (body, expected) in [
+            ("", ".\r\n"),
+            ("hi", "hi\r\n.\r\n"),
+            ("hi\r\n", "hi\r\n.\r\n"),
+            (".", "..\r\n.\r\n"),
+            ("a\n.b\r.c\r\n..d", "a\r\n..b\r\n..c\r\n...d\r\n.\r\n"),
+            ("a\r\r\nb\n\n", "a\r\n\r\nb\r\n\r\n.\r\n"),
+            ("x.y\r\n.\r\n", "x.y\r\n..\r\n.\r\n"),
+        ] {
+            // Every split point must give the same output
+            for split in 0..=body.len() {
+                let mut encoder = DataEncoder::new();
+                let mut buf = Vec::new();
+                encoder.encode(&body.as_bytes()[..split], &mut buf).unwrap();
+                encoder.encode(&body.as_bytes()[split..], &mut buf).unwrap();
+                encoder.finish(&mut buf).unwrap();
+                assert_eq!(String::from_utf8(buf).unwrap(), expected, "{body:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn data_encoder_round_trip() {
+        let mut next = xorshift(0x2545f4914f6cdd1d);
+
+        for _ in 0..2000 {
+            let body = (0..next() % 64)
+                .map(|_| b".\r\nab"[(next() % 5) as usize])
+                .collect::<Vec<_>>();
+
+            let mut encoder = DataEncoder::new();
+            let mut encoded = Vec::new();
+            for chunk in body.chunks(1 + (next() % 8) as usize) {
+                encoder.encode(chunk, &mut encoded).unwrap();
+            }
+            encoder.finish(&mut encoded).unwrap();
+
+            let mut receiver = DataReceiver::new();
+            let mut received = Vec::new();
+            let mut chunks = encoded.chunks(1 + (next() % 8) as usize);
+            assert!(
+                chunks.any(|chunk| receiver.ingest(&mut chunk.iter(), &mut received)),
+                "{body:?}"
+            );
+            assert!(chunks.next().is_none(), "{body:?}");
+
+            assert_eq!(received, normalize_line_breaks(&body), "{body:?}");
+        }
+    }
+
+    #[test]
+    fn bdat_writer() {
+        for (body, chunk_size, writes, expected) in [
+            ("", 4, vec![], "BDAT 0 LAST\r\n"),
+            ("abc", 4, vec![3], "BDAT 3 LAST\r\nabc"),
+            ("abcd", 4, vec![4], "BDAT 4 LAST\r\nabcd"),
+            ("abcde", 4, vec![5], "BDAT 4\r\nabcdBDAT 1 LAST\r\ne"),
+            (
+                "abcdefghij",
+                3,
+                vec![1, 1, 8],
+                "BDAT 3\r\nabcBDAT 3\r\ndefBDAT 3\r\nghiBDAT 1 LAST\r\nj",
+            ),
+            (
+                "ab\r\n.\r\n",
+                1,
+                vec![7],
+                "BDAT 1\r\naBDAT 1\r\nbBDAT 1\r\n\rBDAT 1\r\n\nBDAT 1\r\n.BDAT 1\r\n\rBDAT 1 LAST\r\n\n",
+            ),
+        ] {
+            let mut writer = BdatWriter::new(NonZeroUsize::new(chunk_size).unwrap());
+            let mut buf = Vec::new();
+            let mut chunks = 0;
+            let mut bytes = body.as_bytes();
+            for len in writes {
+                let (head, rest) = bytes.split_at(len);
+                chunks += writer.write(head, &mut buf).unwrap();
+                bytes = rest;
+            }
+            writer.finish(&mut buf).unwrap();
+            assert_eq!(
+                String::from_utf8(buf.clone()).unwrap(),
+                expected,
+                "{body:?}"
+            );
+            assert_eq!(chunks + 1, expected.matches("BDAT").count());
+
+            // Read back the pipelined commands
+            let mut received = Vec::new();
+            let mut iter = buf.iter();
+            loop {
+                match Request::parse(&mut iter).unwrap() {
+                    Request::Bdat {
+                        chunk_size,
+                        is_last,
+                    } => {
+                        assert!(
+                            BdatReceiver::new(chunk_size, is_last).ingest(&mut iter, &mut received)
+                        );
+                        if is_last {
+                            break;
+                        }
+                    }
+                    request => panic!("Unexpected request {request:?}"),
+                }
+            }
+            assert_eq!(iter.len(), 0);
+            assert_eq!(String::from_utf8(received).unwrap(), body);
+        }
+    }
+}
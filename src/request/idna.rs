@@ -0,0 +1,401 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs LLC <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: Apache-2.0 OR MIT
+ */
+
+//! Conversion of mailbox and `EHLO` domains between U-labels and A-labels,
+//! used to relay to next hops that do not support `SMTPUTF8`. As in IDNA2008,
+//! U-labels are not mapped: upper case letters, compatibility characters and
+//! ignored code points such as variation selectors are rejected instead of
+//! being converted as UTS #46 would. The IDNA2008 tables (RFC 5892) are
+//! approximated by allowing only letters, digits, combining marks and joiners
+//! that UTS #46 leaves unchanged, while the remaining label rules (hyphens,
+//! joiner context, bidi) and the punycode conversion are those of UTS #46.
+
+use std::borrow::Cow;
+
+use ::idna::{
+    punycode,
+    uts46::{AsciiDenyList, DnsLength, Hyphens, Uts46},
+};
+use unicode_normalization::char::is_combining_mark;
+
+use crate::{ForwardPath, IdnaError, ReversePath};
+
+/// Converts a domain to A-labels. ASCII domains and address literals are
+/// returned unchanged.
+pub fn domain_to_ascii(domain: &str) -> Result<Cow<'_, str>, IdnaError> {
+    if domain.is_ascii() {
+        return Ok(Cow::Borrowed(domain));
+    }
+    for label in domain.split('.').filter(|label| !label.is_ascii()) {
+        check_u_label(label, label)?;
+    }
+    to_ascii(domain).map_err(|_| {
+        domain
+            .split('.')
+            .find_map(|label| {
+                to_ascii(label).err().map(|_| IdnaError::InvalidLabel {
+                    label: label.to_string(),
+                    // Only ASCII labels are left, which are case-insensitive
+                    disallowed: label
+                        .chars()
+                        .find(|&ch| is_disallowed(ch.to_ascii_lowercase())),
+                })
+            })
+            .unwrap_or(IdnaError::TooLong)
+    })
+}
+
+/// Converts the A-labels of a domain to U-labels. Domains without A-labels are
+/// returned unchanged.
+pub fn domain_to_unicode(domain: &str) -> Result<Cow<'_, str>, IdnaError> {
+    if !domain.split('.').any(is_a_label) {
+        return Ok(Cow::Borrowed(domain));
+    }
+    let (unicode, result) =
+        Uts46::new().to_unicode(domain.as_bytes(), AsciiDenyList::STD3, Hyphens::Check);
+    match result {
+        Ok(()) => {
+            // UTS #46 accepts symbols that IDNA2008 does not
+            for (label, u_label) in domain.split('.').zip(unicode.split('.')) {
+                if is_a_label(label) {
+                    check_u_label(u_label, label)?;
+                }
+            }
+            Ok(Cow::Owned(unicode.into_owned()))
+        }
+        Err(_) => Err(domain
+            .split('.')
+            .find(|label| {
+                Uts46::new()
+                    .to_unicode(label.as_bytes(), AsciiDenyList::STD3, Hyphens::Check)
+                    .1
+                    .is_err()
+            })
+            .map(|label| IdnaError::InvalidLabel {
+                label: label.to_string(),
+                disallowed: is_a_label(label)
+                    .then(|| punycode::decode_to_string(&label[4..]))
+                    .flatten()
+                    .and_then(|label| label.chars().find(|&ch| is_disallowed(ch))),
+            })
+            .unwrap_or(IdnaError::TooLong)),
+    }
+}
+
+/// Converts the domain of a mailbox to A-labels. Fails with
+/// [`IdnaError::NonAsciiLocalPart`] when the local part is not ASCII, as such
+/// a mailbox can only be relayed with `SMTPUTF8`.
+pub fn mailbox_to_ascii(mailbox: &str) -> Result<Cow<'_, str>, IdnaError> {
+    convert_mailbox(mailbox, |local_part, domain| {
+        if local_part.is_ascii() {
+            domain_to_ascii(domain)
+        } else {
+            Err(IdnaError::NonAsciiLocalPart)
+        }
+    })
+}
+
+/// Converts the A-labels in the domain of a mailbox to U-labels.
+pub fn mailbox_to_unicode(mailbox: &str) -> Result<Cow<'_, str>, IdnaError> {
+    convert_mailbox(mailbox, |_, domain| domain_to_unicode(domain))
+}
+
+impl<T: AsRef<str>> ReversePath<T> {
+    /// Converts the mailbox domain to A-labels, see [`mailbox_to_ascii`].
+    pub fn to_ascii(&self) -> Result<ReversePath<Cow<'_, str>>, IdnaError> {
+        Ok(match self {
+            ReversePath::Mailbox(mailbox) => {
+                ReversePath::Mailbox(mailbox_to_ascii(mailbox.as_ref())?)
+            }
+            ReversePath::Null => ReversePath::Null,
+        })
+    }
+
+    /// Converts the mailbox domain to U-labels, see [`mailbox_to_unicode`].
+    pub fn to_unicode(&self) -> Result<ReversePath<Cow<'_, str>>, IdnaError> {
+        Ok(match self {
+            ReversePath::Mailbox(mailbox) => {
+                ReversePath::Mailbox(mailbox_to_unicode(mailbox.as_ref())?)
+            }
+            ReversePath::Null => ReversePath::Null,
+        })
+    }
+}
+
+impl<T: AsRef<str>> ForwardPath<T> {
+    /// Converts the mailbox domain to A-labels, see [`mailbox_to_ascii`].
+    pub fn to_ascii(&self) -> Result<ForwardPath<Cow<'_, str>>, IdnaError> {
+        Ok(match self {
+            ForwardPath::Mailbox(mailbox) => {
+                ForwardPath::Mailbox(mailbox_to_ascii(mailbox.as_ref())?)
+            }
+            ForwardPath::Postmaster => ForwardPath::Postmaster,
+        })
+    }
+
+    /// Converts the mailbox domain to U-labels, see [`mailbox_to_unicode`].
+    pub fn to_unicode(&self) -> Result<ForwardPath<Cow<'_, str>>, IdnaError> {
+        Ok(match self {
+            ForwardPath::Mailbox(mailbox) => {
+                ForwardPath::Mailbox(mailbox_to_unicode(mailbox.as_ref())?)
+            }
+            ForwardPath::Postmaster => ForwardPath::Postmaster,
+        })
+    }
+}
+
+fn convert_mailbox<'x>(
+    mailbox: &'x str,
+    convert: impl FnOnce(&'x str, &'x str) -> Result<Cow<'x, str>, IdnaError>,
+) -> Result<Cow<'x, str>, IdnaError> {
+    let Some((local_part, domain)) = mailbox.rsplit_once('@') else {
+        return Ok(Cow::Borrowed(mailbox));
+    };
+    if domain.starts_with('[') {
+        return convert(local_part, "").map(|_| Cow::Borrowed(mailbox));
+    }
+    Ok(match convert(local_part, domain)? {
+        Cow::Borrowed(_) => Cow::Borrowed(mailbox),
+        Cow::Owned(domain) => Cow::Owned(format!("{local_part}@{domain}")),
+    })
+}
+
+fn to_ascii(domain: &str) -> Result<Cow<'_, str>, ::idna::Errors> {
+    Uts46::new().to_ascii(
+        domain.as_bytes(),
+        AsciiDenyList::STD3,
+        Hyphens::Check,
+        DnsLength::VerifyAllowRootDot,
+    )
+}
+
+fn is_a_label(label: &str) -> bool {
+    label
+        .get(..4)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("xn--"))
+}
+
+/// Fails unless `u_label` is a U-label as it should appear on the wire, which
+/// UTS #46 processing leaves unchanged. Errors refer to `label`.
+fn check_u_label(u_label: &str, label: &str) -> Result<(), IdnaError> {
+    let disallowed = u_label.chars().find(|&ch| is_disallowed(ch));
+    if disallowed.is_none()
+        && Uts46::new()
+            .to_unicode(u_label.as_bytes(), AsciiDenyList::STD3, Hyphens::Check)
+            .0
+            == u_label
+    {
+        Ok(())
+    } else {
+        Err(IdnaError::InvalidLabel {
+            label: label.to_string(),
+            disallowed,
+        })
+    }
+}
+
+/// Returns `true` for code points that are rejected on their own: symbols,
+/// punctuation and anything UTS #46 would map, such as upper case letters.
+/// Combining marks and joiners are only invalid in some contexts, which is
+/// checked on the whole label.
+fn is_disallowed(ch: char) -> bool {
+    if ch.is_ascii() {
+        return !(ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '-');
+    }
+    let ch_str = ch.encode_utf8(&mut [0; 4]).to_string();
+    let (mapped, result) =
+        Uts46::new().to_unicode(ch_str.as_bytes(), AsciiDenyList::STD3, Hyphens::Allow);
+    if is_combining_mark(ch) || matches!(ch, '\u{200c}' | '\u{200d}') {
+        // On their own, these fail the context rules, unless they are mapped
+        result.is_ok() && mapped != ch_str
+    } else {
+        !ch.is_alphanumeric() || result.is_err() || mapped != ch_str
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::{domain_to_ascii, domain_to_unicode, mailbox_to_ascii, mailbox_to_unicode};
+    use crate::{ForwardPath, IdnaError, ReversePath};
+
+    #[test]
+    fn idna_conversion() {
+        for (domain, ascii) in [
+            ("example.org", Ok("example.org")),
+            ("Example.ORG", Ok("Example.ORG")),
+            ("bücher.example", Ok("xn--bcher-kva.example")),
+            ("例子.广告", Ok("xn--fsqu00a.xn--4rr70v")),
+            ("παράδειγμα.δοκιμή", Ok("xn--hxajbheg2az3al.xn--jxalpdlp")),
+            (
+                "b\u{fe0f}ücher.example",
+                Err(IdnaError::InvalidLabel {
+                    label: "b\u{fe0f}ücher".to_string(),
+                    disallowed: Some('\u{fe0f}'),
+                }),
+            ),
+            (
+                "Bücher.example",
+                Err(IdnaError::InvalidLabel {
+                    label: "Bücher".to_string(),
+                    disallowed: Some('B'),
+                }),
+            ),
+            (
+                "ｂücher.example",
+                Err(IdnaError::InvalidLabel {
+                    label: "ｂücher".to_string(),
+                    disallowed: Some('ｂ'),
+                }),
+            ),
+            (
+                "bu\u{308}cher.example",
+                Err(IdnaError::InvalidLabel {
+                    label: "bu\u{308}cher".to_string(),
+                    disallowed: None,
+                }),
+            ),
+            (
+                "i\u{2764}ü.example",
+                Err(IdnaError::InvalidLabel {
+                    label: "i\u{2764}ü".to_string(),
+                    disallowed: Some('\u{2764}'),
+                }),
+            ),
+            (
+                "bücher.Exa_mple",
+                Err(IdnaError::InvalidLabel {
+                    label: "Exa_mple".to_string(),
+                    disallowed: Some('_'),
+                }),
+            ),
+            (
+                "bü_cher.example",
+                Err(IdnaError::InvalidLabel {
+                    label: "bü_cher".to_string(),
+                    disallowed: Some('_'),
+                }),
+            ),
+            (
+                "ü\u{e000}.example",
+                Err(IdnaError::InvalidLabel {
+                    label: "ü\u{e000}".to_string(),
+                    disallowed: Some('\u{e000}'),
+                }),
+            ),
+            (
+                "-bücher.example",
+                Err(IdnaError::InvalidLabel {
+                    label: "-bücher".to_string(),
+                    disallowed: None,
+                }),
+            ),
+            (
+                "\u{301}a.example",
+                Err(IdnaError::InvalidLabel {
+                    label: "\u{301}a".to_string(),
+                    disallowed: None,
+                }),
+            ),
+            (
+                &format!("{}ü.example", "a".repeat(60)),
+                Err(IdnaError::InvalidLabel {
+                    label: format!("{}ü", "a".repeat(60)),
+                    disallowed: None,
+                }),
+            ),
+        ] {
+            assert_eq!(
+                domain_to_ascii(domain),
+                ascii.map(Cow::Borrowed),
+                "{domain:?}"
+            );
+        }
+
+        for (domain, unicode) in [
+            ("example.org", Ok("example.org")),
+            ("xn--bcher-kva.example", Ok("bücher.example")),
+            ("XN--BCHER-KVA.example", Ok("bücher.example")),
+            ("xn--fsqu00a.xn--4rr70v", Ok("例子.广告")),
+            (
+                "xn---b-yka.example",
+                Err(IdnaError::InvalidLabel {
+                    label: "xn---b-yka".to_string(),
+                    disallowed: None,
+                }),
+            ),
+            (
+                "xn--i-7iq.example",
+                Err(IdnaError::InvalidLabel {
+                    label: "xn--i-7iq".to_string(),
+                    disallowed: Some('\u{2764}'),
+                }),
+            ),
+            (
+                "xn--tda8925i.example",
+                Err(IdnaError::InvalidLabel {
+                    label: "xn--tda8925i".to_string(),
+                    disallowed: Some('\u{e000}'),
+                }),
+            ),
+        ] {
+            assert_eq!(
+                domain_to_unicode(domain),
+                unicode.map(Cow::Borrowed),
+                "{domain:?}"
+            );
+        }
+
+        for (mailbox, ascii, unicode) in [
+            (
+                "jdoe@bücher.example",
+                Ok("jdoe@xn--bcher-kva.example"),
+                Ok("jdoe@bücher.example"),
+            ),
+            (
+                "jdoe@xn--bcher-kva.example",
+                Ok("jdoe@xn--bcher-kva.example"),
+                Ok("jdoe@bücher.example"),
+            ),
+            (
+                "jdoe@[192.0.2.1]",
+                Ok("jdoe@[192.0.2.1]"),
+                Ok("jdoe@[192.0.2.1]"),
+            ),
+            (
+                "用户@例子.广告",
+                Err(IdnaError::NonAsciiLocalPart),
+                Ok("用户@例子.广告"),
+            ),
+        ] {
+            assert_eq!(
+                mailbox_to_ascii(mailbox),
+                ascii.map(Cow::Borrowed),
+                "{mailbox:?}"
+            );
+            assert_eq!(
+                mailbox_to_unicode(mailbox),
+                unicode.map(Cow::Borrowed),
+                "{mailbox:?}"
+            );
+        }
+
+        assert_eq!(
+            ReversePath::Mailbox("jdoe@bücher.example").to_ascii(),
+            Ok(ReversePath::Mailbox("jdoe@xn--bcher-kva.example".into()))
+        );
+        assert_eq!(ReversePath::<&str>::Null.to_ascii(), Ok(ReversePath::Null));
+        assert_eq!(
+            ForwardPath::Mailbox("jdoe@xn--bcher-kva.example").to_unicode(),
+            Ok(ForwardPath::Mailbox("jdoe@bücher.example".into()))
+        );
+        assert_eq!(
+            ForwardPath::<&str>::Postmaster.to_ascii(),
+            Ok(ForwardPath::Postmaster)
+        );
+    }
+}
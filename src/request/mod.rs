@@ -6,8 +6,13 @@
 
 use crate::tokens::{define_tokens_128, define_tokens_64};
 
+pub mod encoder;
+#[cfg(feature = "idna")]
+pub mod idna;
+mod params;
 pub mod parser;
 pub mod receiver;
+mod smtputf8;
 
 pub const AUTH: u64 = crate::tokens::token64("AUTH"); // special, because it is `pub` instead of `pub(crate)`
 
@@ -0,0 +1,233 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs LLC <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: Apache-2.0 OR MIT
+ */
+
+//! Conversions between the typed MAIL FROM and RCPT TO parameters and the
+//! legacy `MAIL_*` and `RCPT_*` bitmasks.
+
+use crate::*;
+
+impl Body {
+    /// Reads the body type from legacy flags. When several body bits are set,
+    /// `BINARYMIME` takes precedence over `8BITMIME`, which takes precedence over `7BIT`.
+    pub fn from_flags(flags: u64) -> Option<Self> {
+        if flags & MAIL_BODY_BINARYMIME != 0 {
+            Some(Body::BinaryMime)
+        } else if flags & MAIL_BODY_8BITMIME != 0 {
+            Some(Body::EightBitMime)
+        } else if flags & MAIL_BODY_7BIT != 0 {
+            Some(Body::SevenBit)
+        } else {
+            None
+        }
+    }
+
+    pub fn to_flags(&self) -> u64 {
+        match self {
+            Body::SevenBit => MAIL_BODY_7BIT,
+            Body::EightBitMime => MAIL_BODY_8BITMIME,
+            Body::BinaryMime => MAIL_BODY_BINARYMIME,
+        }
+    }
+}
+
+impl Ret {
+    /// Reads the DSN return type from legacy flags, preferring `FULL` over `HDRS`.
+    pub fn from_flags(flags: u64) -> Option<Self> {
+        if flags & MAIL_RET_FULL != 0 {
+            Some(Ret::Full)
+        } else if flags & MAIL_RET_HDRS != 0 {
+            Some(Ret::Hdrs)
+        } else {
+            None
+        }
+    }
+
+    pub fn to_flags(&self) -> u64 {
+        match self {
+            Ret::Full => MAIL_RET_FULL,
+            Ret::Hdrs => MAIL_RET_HDRS,
+        }
+    }
+}
+
+impl DeliverBy {
+    /// Reads the delivery mode from legacy flags, preferring `R` over `N`.
+    pub fn from_flags(time: i64, flags: u64) -> Option<Self> {
+        let mode = if flags & MAIL_BY_RETURN != 0 {
+            DeliverByMode::Return
+        } else if flags & MAIL_BY_NOTIFY != 0 {
+            DeliverByMode::Notify
+        } else {
+            return None;
+        };
+        Some(DeliverBy {
+            time,
+            mode,
+            trace: flags & MAIL_BY_TRACE != 0,
+        })
+    }
+
+    pub fn to_flags(&self) -> u64 {
+        (match self.mode {
+            DeliverByMode::Notify => MAIL_BY_NOTIFY,
+            DeliverByMode::Return => MAIL_BY_RETURN,
+        }) | if self.trace { MAIL_BY_TRACE } else { 0 }
+    }
+}
+
+impl NotifySet {
+    pub const NEVER: NotifySet = NotifySet {
+        success: false,
+        failure: false,
+        delay: false,
+    };
+
+    pub fn is_never(&self) -> bool {
+        *self == Self::NEVER
+    }
+
+    /// Reads the DSN conditions from legacy flags. `NEVER` is ignored when
+    /// any other condition is also set.
+    pub fn from_flags(flags: u64) -> Option<Self> {
+        let notify = NotifySet {
+            success: flags & RCPT_NOTIFY_SUCCESS != 0,
+            failure: flags & RCPT_NOTIFY_FAILURE != 0,
+            delay: flags & RCPT_NOTIFY_DELAY != 0,
+        };
+        if !notify.is_never() || flags & RCPT_NOTIFY_NEVER != 0 {
+            Some(notify)
+        } else {
+            None
+        }
+    }
+
+    pub fn to_flags(&self) -> u64 {
+        if self.is_never() {
+            RCPT_NOTIFY_NEVER
+        } else {
+            (if self.success { RCPT_NOTIFY_SUCCESS } else { 0 })
+                | (if self.failure { RCPT_NOTIFY_FAILURE } else { 0 })
+                | (if self.delay { RCPT_NOTIFY_DELAY } else { 0 })
+        }
+    }
+}
+
+impl Rrvs {
+    /// Reads the RRVS action from legacy flags, preferring `R` over `C`.
+    pub fn from_flags(time: i64, flags: u64) -> Option<Self> {
+        let on_fail = if flags & RCPT_RRVS_REJECT != 0 {
+            RrvsAction::Reject
+        } else if flags & RCPT_RRVS_CONTINUE != 0 {
+            RrvsAction::Continue
+        } else {
+            return None;
+        };
+        Some(Rrvs { time, on_fail })
+    }
+
+    pub fn to_flags(&self) -> u64 {
+        match self.on_fail {
+            RrvsAction::Reject => RCPT_RRVS_REJECT,
+            RrvsAction::Continue => RCPT_RRVS_CONTINUE,
+        }
+    }
+}
+
+impl<T> MailFrom<T> {
+    /// Returns the parameters as a legacy `MAIL_*` bitmask.
+    pub fn flags(&self) -> u64 {
+        self.body.as_ref().map_or(0, Body::to_flags)
+            | self.ret.as_ref().map_or(0, Ret::to_flags)
+            | self.by.as_ref().map_or(0, DeliverBy::to_flags)
+            | if self.smtp_utf8 { MAIL_SMTPUTF8 } else { 0 }
+            | if self.require_tls { MAIL_REQUIRETLS } else { 0 }
+            | if self.conperm { MAIL_CONPERM } else { 0 }
+    }
+
+    /// Sets the parameters from a legacy `MAIL_*` bitmask and the `BY` time.
+    pub fn set_flags(&mut self, flags: u64, by: i64) {
+        self.body = Body::from_flags(flags);
+        self.ret = Ret::from_flags(flags);
+        self.by = DeliverBy::from_flags(by, flags);
+        self.smtp_utf8 = flags & MAIL_SMTPUTF8 != 0;
+        self.require_tls = flags & MAIL_REQUIRETLS != 0;
+        self.conperm = flags & MAIL_CONPERM != 0;
+    }
+}
+
+impl<T> RcptTo<T> {
+    /// Returns the parameters as a legacy `RCPT_*` bitmask.
+    pub fn flags(&self) -> u64 {
+        self.notify.as_ref().map_or(0, NotifySet::to_flags)
+            | self.rrvs.as_ref().map_or(0, Rrvs::to_flags)
+            | if self.conneg { RCPT_CONNEG } else { 0 }
+    }
+
+    /// Sets the parameters from a legacy `RCPT_*` bitmask and the `RRVS` time.
+    pub fn set_flags(&mut self, flags: u64, rrvs: i64) {
+        self.notify = NotifySet::from_flags(flags);
+        self.rrvs = Rrvs::from_flags(rrvs, flags);
+        self.conneg = flags & RCPT_CONNEG != 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn legacy_flags() {
+        for (flags, by) in [
+            (0, 0),
+            (MAIL_BODY_7BIT | MAIL_RET_FULL, 0),
+            (MAIL_BODY_8BITMIME | MAIL_RET_HDRS | MAIL_SMTPUTF8, 0),
+            (MAIL_BODY_BINARYMIME | MAIL_REQUIRETLS | MAIL_CONPERM, 0),
+            (MAIL_BY_NOTIFY, 120),
+            (MAIL_BY_RETURN | MAIL_BY_TRACE, -10),
+        ] {
+            let mut from = MailFrom::<String>::default();
+            from.set_flags(flags, by);
+            assert_eq!(from.flags(), flags);
+            assert_eq!(from.by.map_or(0, |by| by.time), by);
+        }
+
+        for (flags, rrvs) in [
+            (0, 0),
+            (RCPT_NOTIFY_NEVER, 0),
+            (
+                RCPT_NOTIFY_SUCCESS | RCPT_NOTIFY_FAILURE | RCPT_NOTIFY_DELAY,
+                0,
+            ),
+            (RCPT_NOTIFY_DELAY | RCPT_CONNEG, 0),
+            (RCPT_RRVS_REJECT, 1396566060),
+            (RCPT_RRVS_CONTINUE | RCPT_NOTIFY_FAILURE, 880410121),
+        ] {
+            let mut to = RcptTo::<String>::default();
+            to.set_flags(flags, rrvs);
+            assert_eq!(to.flags(), flags);
+            assert_eq!(to.rrvs.map_or(0, |rrvs| rrvs.time), rrvs);
+        }
+
+        // Contradictory legacy combinations collapse into a single value
+        let mut from = MailFrom::<String>::default();
+        from.set_flags(
+            MAIL_BODY_7BIT | MAIL_BODY_8BITMIME | MAIL_RET_HDRS | MAIL_RET_FULL,
+            0,
+        );
+        assert_eq!(from.body, Some(Body::EightBitMime));
+        assert_eq!(from.ret, Some(Ret::Full));
+
+        let mut to = RcptTo::<String>::default();
+        to.set_flags(RCPT_NOTIFY_NEVER | RCPT_NOTIFY_SUCCESS, 0);
+        assert_eq!(
+            to.notify,
+            Some(NotifySet {
+                success: true,
+                ..Default::default()
+            })
+        );
+    }
+}
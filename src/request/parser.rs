@@ -16,13 +16,61 @@ const MAX_ADDRESS_LEN: usize = 256;
 const MAX_DOMAIN_LEN: usize = 255;
 
 impl<'a> Request<Cow<'a, str>> {
+    /// Parses a command. Invalid UTF-8 is replaced with U+FFFD, use
+    /// [`Request::parse_raw`] to obtain the exact bytes.
     pub fn parse(bytes: &mut Iter<'a, u8>) -> Result<Request<Cow<'a, str>>, Error> {
+        Request::parse_raw(bytes).map(|request| request.map(into_utf8_lossy))
+    }
+
+    /// Parses a command, collecting errors in `MAIL FROM` and `RCPT TO` parameters
+    /// instead of failing. Invalid parameters are left unset in the returned request.
+    pub fn parse_lenient(
+        bytes: &mut Iter<'a, u8>,
+    ) -> Result<(Request<Cow<'a, str>>, Vec<Error>), Error> {
+        Request::parse_raw_lenient(bytes)
+            .map(|(request, errors)| (request.map(into_utf8_lossy), errors))
+    }
+}
+
+impl<'a> Request<Cow<'a, [u8]>> {
+    /// Parses a command without decoding its values as UTF-8.
+    pub fn parse_raw(bytes: &mut Iter<'a, u8>) -> Result<Request<Cow<'a, [u8]>>, Error> {
+        Self::parse_command(&mut Rfc5321Parser::new(bytes))
+    }
+
+    /// Same as [`Request::parse_lenient`], without decoding values as UTF-8.
+    pub fn parse_raw_lenient(bytes: &mut Iter<'a, u8>) -> Result<(Self, Vec<Error>), Error> {
         let mut parser = Rfc5321Parser::new(bytes);
+        parser.errors = Some(Vec::new());
+        let request = Self::parse_command(&mut parser)?;
+        Ok((request, parser.errors.unwrap_or_default()))
+    }
+
+    fn parse_command(parser: &mut Rfc5321Parser<'_, 'a>) -> Result<Request<Cow<'a, [u8]>>, Error> {
         let command = parser.hashed_value()?;
         if !parser.stop_char.is_ascii_whitespace() {
             parser.seek_lf()?;
             return Err(Error::UnknownCommand);
         }
+        parser.verb = match command {
+            RCPT => "RCPT",
+            MAIL => "MAIL",
+            EHLO => "EHLO",
+            BDAT => "BDAT",
+            AUTH => "AUTH",
+            EXPN => "EXPN",
+            LHLO => "LHLO",
+            VRFY => "VRFY",
+            ETRN => "ETRN",
+            ATRN => "ATRN",
+            BURL => "BURL",
+            HELO => "HELO",
+            _ => "",
+        };
+        parser.args_start = parser.start[..parser.offset() - 1]
+            .iter()
+            .rposition(|ch| !ch.is_ascii_whitespace())
+            .map_or(0, |pos| pos + 1);
 
         match command {
             RCPT => {
@@ -31,21 +79,28 @@ impl<'a> Request<Cow<'a, str>> {
                     || parser.stop_char != b':' && parser.next_char()? != b':')
                     && parser.next_char()? == b'<'
                 {
-                    if let Some(to) = parser.address()? {
-                        if parser.stop_char == b'>' {
+                    let address_start = parser.offset();
+                    match parser.address()? {
+                        Some(_) if parser.stop_char != b'>' => (),
+                        Some(to) if !to.is_empty() => {
+                            let to = if to.eq_ignore_ascii_case(b"postmaster") {
+                                ForwardPath::Postmaster
+                            } else {
+                                ForwardPath::Mailbox(to)
+                            };
                             return Ok(Request::Rcpt {
                                 to: parser.rcpt_to_parameters(to)?,
                             });
                         }
-                    } else {
-                        parser.seek_lf()?;
-                        return Err(Error::InvalidRecipientAddress);
+                        _ => {
+                            let context = parser.invalid_address(address_start);
+                            parser.seek_lf()?;
+                            return Err(Error::InvalidRecipientAddress { context });
+                        }
                     }
                 }
                 parser.seek_lf()?;
-                Err(Error::SyntaxError {
-                    syntax: "RCPT TO:<forward-path> [parameters]",
-                })
+                Err(parser.syntax_error("RCPT TO:<forward-path> [parameters]"))
             }
             MAIL => {
                 if !(parser.stop_char == LF
@@ -53,22 +108,29 @@ impl<'a> Request<Cow<'a, str>> {
                     || parser.stop_char != b':' && parser.next_char()? != b':')
                     && parser.next_char()? == b'<'
                 {
-                    if let Some(from) = parser.address()? {
-                        if parser.stop_char == b'>' {
+                    let address_start = parser.offset();
+                    match parser.address()? {
+                        Some(_) if parser.stop_char != b'>' => (),
+                        Some(from) => {
+                            let from = if from.is_empty() {
+                                ReversePath::Null
+                            } else {
+                                ReversePath::Mailbox(from)
+                            };
                             return Ok(Request::Mail {
                                 from: parser.mail_from_parameters(from)?,
                             });
                         }
-                    } else {
-                        parser.seek_lf()?;
-                        return Err(Error::InvalidSenderAddress);
+                        None => {
+                            let context = parser.invalid_address(address_start);
+                            parser.seek_lf()?;
+                            return Err(Error::InvalidSenderAddress { context });
+                        }
                     }
                 }
 
                 parser.seek_lf()?;
-                Err(Error::SyntaxError {
-                    syntax: "MAIL FROM:<reverse-path> [parameters]",
-                })
+                Err(parser.syntax_error("MAIL FROM:<reverse-path> [parameters]"))
             }
             DATA => {
                 parser.seek_lf()?;
@@ -82,9 +144,7 @@ impl<'a> Request<Cow<'a, str>> {
                         return Ok(Request::Ehlo { host });
                     }
                 }
-                Err(Error::SyntaxError {
-                    syntax: "EHLO domain",
-                })
+                Err(parser.syntax_error("EHLO domain"))
             }
             BDAT => {
                 if parser.stop_char != LF {
@@ -117,9 +177,7 @@ impl<'a> Request<Cow<'a, str>> {
                     }
                 }
                 parser.seek_lf()?;
-                Err(Error::SyntaxError {
-                    syntax: "BDAT chunk-size [LAST]",
-                })
+                Err(parser.syntax_error("BDAT chunk-size [LAST]"))
             }
             AUTH => {
                 if parser.stop_char != LF
@@ -128,7 +186,7 @@ impl<'a> Request<Cow<'a, str>> {
                     let initial_response = if parser.stop_char != LF {
                         parser.text()?
                     } else {
-                        Cow::Borrowed("")
+                        Cow::Borrowed(&[][..])
                     };
                     parser.seek_lf()?;
                     return Ok(Request::Auth {
@@ -136,9 +194,8 @@ impl<'a> Request<Cow<'a, str>> {
                         initial_response,
                     });
                 }
-                Err(Error::SyntaxError {
-                    syntax: "AUTH mechanism [initial-response]",
-                })
+                parser.seek_lf()?;
+                Err(parser.syntax_error("AUTH mechanism [initial-response]"))
             }
             EXPN => {
                 if parser.stop_char != LF {
@@ -148,9 +205,7 @@ impl<'a> Request<Cow<'a, str>> {
                         return Ok(Request::Expn { value });
                     }
                 }
-                Err(Error::SyntaxError {
-                    syntax: "EXPN string",
-                })
+                Err(parser.syntax_error("EXPN string"))
             }
             NOOP => {
                 if parser.stop_char != LF {
@@ -159,7 +214,7 @@ impl<'a> Request<Cow<'a, str>> {
                     Ok(Request::Noop { value })
                 } else {
                     Ok(Request::Noop {
-                        value: Cow::Borrowed(""),
+                        value: Cow::Borrowed(&[][..]),
                     })
                 }
             }
@@ -175,9 +230,7 @@ impl<'a> Request<Cow<'a, str>> {
                         return Ok(Request::Lhlo { host });
                     }
                 }
-                Err(Error::SyntaxError {
-                    syntax: "LHLO domain",
-                })
+                Err(parser.syntax_error("LHLO domain"))
             }
             RSET => {
                 parser.seek_lf()?;
@@ -191,9 +244,7 @@ impl<'a> Request<Cow<'a, str>> {
                         return Ok(Request::Vrfy { value });
                     }
                 }
-                Err(Error::SyntaxError {
-                    syntax: "VRFY string",
-                })
+                Err(parser.syntax_error("VRFY string"))
             }
             HELP => {
                 if parser.stop_char != LF {
@@ -202,7 +253,7 @@ impl<'a> Request<Cow<'a, str>> {
                     Ok(Request::Help { value })
                 } else {
                     Ok(Request::Help {
-                        value: Cow::Borrowed(""),
+                        value: Cow::Borrowed(&[][..]),
                     })
                 }
             }
@@ -218,9 +269,7 @@ impl<'a> Request<Cow<'a, str>> {
                         return Ok(Request::Etrn { name });
                     }
                 }
-                Err(Error::SyntaxError {
-                    syntax: "ETRN name",
-                })
+                Err(parser.syntax_error("ETRN name"))
             }
             ATRN => {
                 if parser.stop_char != LF {
@@ -240,9 +289,7 @@ impl<'a> Request<Cow<'a, str>> {
                         }
                     }
                 }
-                Err(Error::SyntaxError {
-                    syntax: "ATRN domain[,domain]",
-                })
+                Err(parser.syntax_error("ATRN domain[,domain]"))
             }
             BURL => {
                 if parser.stop_char != LF {
@@ -272,9 +319,7 @@ impl<'a> Request<Cow<'a, str>> {
                     }
                 }
                 parser.seek_lf()?;
-                Err(Error::SyntaxError {
-                    syntax: "BURL absolute-uri [LAST]",
-                })
+                Err(parser.syntax_error("BURL absolute-uri [LAST]"))
             }
             HELO => {
                 if parser.stop_char != LF {
@@ -284,9 +329,7 @@ impl<'a> Request<Cow<'a, str>> {
                         return Ok(Request::Helo { host });
                     }
                 }
-                Err(Error::SyntaxError {
-                    syntax: "HELO domain",
-                })
+                Err(parser.syntax_error("HELO domain"))
             }
             _ => {
                 parser.seek_lf()?;
@@ -298,6 +341,11 @@ impl<'a> Request<Cow<'a, str>> {
 
 pub struct Rfc5321Parser<'x, 'y> {
     bytes: &'x mut Iter<'y, u8>,
+    start: &'y [u8],
+    verb: &'static str,
+    args_start: usize,
+    errors: Option<Vec<Error>>,
+    route: Vec<Cow<'y, [u8]>>,
     pub stop_char: u8,
     pub bytes_left: usize,
 }
@@ -306,12 +354,120 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
     pub fn new(bytes: &'x mut Iter<'y, u8>) -> Self {
         let (bytes_left, _) = bytes.size_hint();
         Rfc5321Parser {
+            start: bytes.as_slice(),
             bytes,
             bytes_left,
+            verb: "",
+            args_start: 0,
+            errors: None,
+            route: Vec::new(),
             stop_char: 0,
         }
     }
 
+    /// Number of bytes consumed since the parser was created.
+    #[inline(always)]
+    fn offset(&self) -> usize {
+        self.start.len() - self.bytes.as_slice().len()
+    }
+
+    /// Builds the context for the bytes between `start` and `end`, ignoring
+    /// surrounding whitespace.
+    fn context(&self, start: usize, end: usize) -> ErrorContext {
+        let token = &self.start[start..end.max(start)];
+        let leading = token
+            .iter()
+            .position(|ch| !ch.is_ascii_whitespace())
+            .unwrap_or_default();
+        let trailing = token
+            .iter()
+            .rposition(|ch| !ch.is_ascii_whitespace())
+            .map_or(0, |pos| pos + 1);
+        ErrorContext {
+            verb: self.verb,
+            offset: start + leading,
+            len: trailing.saturating_sub(leading),
+        }
+    }
+
+    /// Consumes the rest of the current token, stopping at whitespace or at
+    /// `stop_char`, and returns its context starting at `start`.
+    fn token_context(&mut self, start: usize, stop_char: u8) -> ErrorContext {
+        if !self.stop_char.is_ascii_whitespace() && self.stop_char != stop_char {
+            for &ch in &mut self.bytes {
+                if ch.is_ascii_whitespace() || ch == stop_char {
+                    self.stop_char = ch;
+                    break;
+                }
+            }
+        }
+        let end = if self.stop_char.is_ascii_whitespace() || self.stop_char == stop_char {
+            self.offset() - 1
+        } else {
+            self.offset()
+        };
+        self.context(start, end)
+    }
+
+    /// Records a parameter error in lenient mode, otherwise skips the rest of
+    /// the command line and returns it.
+    fn parameter_error(&mut self, error: Error) -> Result<(), Error> {
+        if let Some(errors) = &mut self.errors {
+            errors.push(error);
+            Ok(())
+        } else {
+            self.seek_lf()?;
+            Err(error)
+        }
+    }
+
+    fn invalid_parameter(&mut self, param: &'static str, start: usize) -> Result<(), Error> {
+        let context = self.token_context(start, LF);
+        self.parameter_error(Error::InvalidParameter { param, context })
+    }
+
+    /// Offset of a keyword just returned by `hashed_value_long`.
+    fn key_start(&self, key: u128) -> usize {
+        let key_len = key.to_le_bytes().iter().filter(|&&ch| ch != 0).count();
+        let mut end = self.offset() - 1;
+        while end > 0 && self.start[end - 1] == b'\r' {
+            end -= 1;
+        }
+        end.saturating_sub(key_len)
+    }
+
+    /// Marks a parameter as seen, returning `false` after recording an error if
+    /// it was already present.
+    fn unique_parameter(
+        &mut self,
+        seen: &mut u32,
+        bit: u32,
+        param: &'static str,
+        start: usize,
+    ) -> Result<bool, Error> {
+        if *seen & bit == 0 {
+            *seen |= bit;
+            Ok(true)
+        } else {
+            let context = self.token_context(start, LF);
+            self.parameter_error(Error::DuplicateParameter { param, context })?;
+            Ok(false)
+        }
+    }
+
+    fn invalid_address(&mut self, start: usize) -> ErrorContext {
+        self.token_context(start, b'>')
+    }
+
+    /// Returns a syntax error spanning the command arguments. Must be called
+    /// once the whole command line has been consumed.
+    fn syntax_error(&self, syntax: &'static str) -> Error {
+        Error::SyntaxError {
+            syntax,
+            context: self.context(self.args_start, self.offset() - 1),
+        }
+    }
+
     #[allow(clippy::while_let_on_iterator)]
     pub fn hashed_value(&mut self) -> Result<u64, Error> {
         let mut value: u64 = 0;
@@ -380,12 +536,13 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
         })
     }
 
-    pub fn address(&mut self) -> Result<Option<Cow<'y, str>>, Error> {
+    pub fn address(&mut self) -> Result<Option<Cow<'y, [u8]>>, Error> {
         let mut value = self.start_zero_copy();
         let mut last_ch = 0;
         let mut in_quote = false;
         let mut at_count = 0;
         let mut lp_len = 0;
+        self.route.clear();
 
         while let Some(&ch) = self.bytes.next() {
             match ch {
@@ -430,16 +587,18 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
 
                     return Ok(match value.len() {
                         1..=MAX_ADDRESS_LEN if at_count == 1 && lp_len > 0 => Some(value),
-                        10 if at_count == 0 && value.eq_ignore_ascii_case("postmaster") => {
-                            Some("".into())
+                        10 if at_count == 0 && value.eq_ignore_ascii_case(b"postmaster") => {
+                            Some(value)
                         }
-                        0 => Some("".into()),
+                        0 => Some(Cow::Borrowed(&[][..])),
                         _ => None,
                     });
                 }
                 b'\r' => self.flush_excluding_current(&mut value),
                 b':' if !in_quote && self.first_excluding_current(&value) == Some(b'@') => {
-                    // Remove source route
+                    // Move the source route out of the mailbox
+                    self.flush_excluding_current(&mut value);
+                    self.push_route(value.data);
                     value = self.start_zero_copy();
                     at_count = 0;
                     lp_len = 0;
@@ -498,7 +657,7 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
         })
     }
 
-    pub fn string(&mut self) -> Result<Cow<'y, str>, Error> {
+    pub fn string(&mut self) -> Result<Cow<'y, [u8]>, Error> {
         let mut in_quote = false;
         let mut value = self.start_zero_copy();
         let mut last_ch = 0;
@@ -542,7 +701,7 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
     }
 
     #[allow(clippy::while_let_on_iterator)]
-    pub fn text(&mut self) -> Result<Cow<'y, str>, Error> {
+    pub fn text(&mut self) -> Result<Cow<'y, [u8]>, Error> {
         let mut value = self.start_zero_copy();
         while let Some(&ch) = self.bytes.next() {
             match ch {
@@ -562,7 +721,7 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
     }
 
     #[allow(clippy::while_let_on_iterator)]
-    pub fn xtext(&mut self) -> Result<Cow<'y, str>, Error> {
+    pub fn xtext(&mut self) -> Result<Cow<'y, [u8]>, Error> {
         let mut value = self.start_zero_copy();
         while let Some(&ch) = self.bytes.next() {
             match ch {
@@ -580,7 +739,7 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
                         if let Some(digit) = char::from(ch).to_digit(16) {
                             if let Some(hex1) = hex1 {
                                 let data = value.data.to_mut();
-                                data.push(char::from(((hex1 as u8) << 4) | digit as u8));
+                                data.push(((hex1 as u8) << 4) | digit as u8);
                                 break;
                             } else {
                                 hex1 = Some(digit);
@@ -606,7 +765,7 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
     }
 
     #[allow(clippy::while_let_on_iterator)]
-    pub fn seek_char(&mut self, stop_char: u8) -> Result<Cow<'y, str>, Error> {
+    pub fn seek_char(&mut self, stop_char: u8) -> Result<Cow<'y, [u8]>, Error> {
         let mut value = self.start_zero_copy();
         while let Some(&ch) = self.bytes.next() {
             match ch {
@@ -807,14 +966,19 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
 
     pub fn mail_from_parameters(
         &mut self,
-        address: Cow<'y, str>,
-    ) -> Result<MailFrom<Cow<'y, str>>, Error> {
+        address: ReversePath<Cow<'y, [u8]>>,
+    ) -> Result<MailFrom<Cow<'y, [u8]>>, Error> {
         let mut params = MailFrom {
             address,
-            flags: 0,
+            route: std::mem::take(&mut self.route),
+            body: None,
+            ret: None,
+            smtp_utf8: false,
+            require_tls: false,
+            conperm: false,
             size: 0,
             trans_id: None,
-            by: 0,
+            by: None,
             env_id: None,
             solicit: None,
             mtrk: None,
@@ -823,53 +987,80 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
             hold_until: 0,
             mt_priority: 0,
         };
+        let mut seen = 0u32;
         while self.stop_char != LF {
             let key = self.hashed_value_long()?;
+            let value_start = self.offset();
+            let key_start = self.key_start(key);
+            let (bit, param) = match key {
+                SMTPUTF8 => (1 << 0, "SMTPUTF8"),
+                BODY => (1 << 1, "BODY"),
+                SIZE => (1 << 2, "SIZE"),
+                BY => (1 << 3, "BY"),
+                HOLDUNTIL => (1 << 4, "HOLDUNTIL"),
+                HOLDFOR => (1 << 5, "HOLDFOR"),
+                RET => (1 << 6, "RET"),
+                ENVID => (1 << 7, "ENVID"),
+                REQUIRETLS => (1 << 8, "REQUIRETLS"),
+                SOLICIT => (1 << 9, "SOLICIT"),
+                TRANSID => (1 << 10, "TRANSID"),
+                MTRK => (1 << 11, "MTRK"),
+                AUTH_ => (1 << 12, "AUTH"),
+                MT_PRIORITY => (1 << 13, "MT-PRIORITY"),
+                CONPERM => (1 << 14, "CONPERM"),
+                _ => (0, ""),
+            };
+            if !self.unique_parameter(&mut seen, bit, param, key_start)? {
+                continue;
+            }
             match key {
                 SMTPUTF8 if self.stop_char.is_ascii_whitespace() => {
-                    params.flags |= MAIL_SMTPUTF8;
+                    params.smtp_utf8 = true;
                 }
                 BODY if self.stop_char == b'=' => {
-                    params.flags |= match self.hashed_value_long()? {
-                        EIGHBITMIME if self.stop_char.is_ascii_whitespace() => MAIL_BODY_8BITMIME,
-                        BINARYMIME if self.stop_char.is_ascii_whitespace() => MAIL_BODY_BINARYMIME,
-                        SEVENBIT if self.stop_char.is_ascii_whitespace() => MAIL_BODY_7BIT,
+                    params.body = match self.hashed_value_long()? {
+                        EIGHBITMIME if self.stop_char.is_ascii_whitespace() => Body::EightBitMime,
+                        BINARYMIME if self.stop_char.is_ascii_whitespace() => Body::BinaryMime,
+                        SEVENBIT if self.stop_char.is_ascii_whitespace() => Body::SevenBit,
                         _ => {
-                            self.seek_lf()?;
-                            return Err(Error::InvalidParameter { param: "BODY" });
+                            self.invalid_parameter("BODY", value_start)?;
+                            continue;
                         }
                     }
+                    .into();
                 }
                 SIZE if self.stop_char == b'=' => {
                     let size = self.size()?;
                     if size != usize::MAX && self.stop_char.is_ascii_whitespace() {
                         params.size = size;
                     } else {
-                        self.seek_lf()?;
-                        return Err(Error::InvalidParameter { param: "SIZE" });
+                        self.invalid_parameter("SIZE", value_start)?;
                     }
                 }
                 BY if self.stop_char == b'=' => {
                     let time = self.integer()?;
                     if time != i64::MAX && self.stop_char == b';' {
-                        params.flags |= match self.hashed_value()? {
-                            N if self.stop_char.is_ascii_whitespace() => MAIL_BY_NOTIFY,
+                        let (mode, trace) = match self.hashed_value()? {
+                            N if self.stop_char.is_ascii_whitespace() => {
+                                (DeliverByMode::Notify, false)
+                            }
                             NT if self.stop_char.is_ascii_whitespace() => {
-                                MAIL_BY_NOTIFY | MAIL_BY_TRACE
+                                (DeliverByMode::Notify, true)
+                            }
+                            R if self.stop_char.is_ascii_whitespace() => {
+                                (DeliverByMode::Return, false)
                             }
-                            R if self.stop_char.is_ascii_whitespace() => MAIL_BY_RETURN,
                             RT if self.stop_char.is_ascii_whitespace() => {
-                                MAIL_BY_RETURN | MAIL_BY_TRACE
+                                (DeliverByMode::Return, true)
                             }
                             _ => {
-                                self.seek_lf()?;
-                                return Err(Error::InvalidParameter { param: "BY" });
+                                self.invalid_parameter("BY", value_start)?;
+                                continue;
                             }
                         };
-                        params.by = time;
+                        params.by = DeliverBy { time, mode, trace }.into();
                     } else {
-                        self.seek_lf()?;
-                        return Err(Error::InvalidParameter { param: "BY" });
+                        self.invalid_parameter("BY", value_start)?;
                     }
                 }
                 HOLDUNTIL if self.stop_char == b'=' => {
@@ -877,8 +1068,7 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
                     if hold != usize::MAX && self.stop_char.is_ascii_whitespace() {
                         params.hold_until = hold as u64;
                     } else {
-                        self.seek_lf()?;
-                        return Err(Error::InvalidParameter { param: "HOLDUNTIL" });
+                        self.invalid_parameter("HOLDUNTIL", value_start)?;
                     }
                 }
                 HOLDFOR if self.stop_char == b'=' => {
@@ -886,39 +1076,37 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
                     if hold != usize::MAX && self.stop_char.is_ascii_whitespace() {
                         params.hold_for = hold as u64;
                     } else {
-                        self.seek_lf()?;
-                        return Err(Error::InvalidParameter { param: "HOLDFOR" });
+                        self.invalid_parameter("HOLDFOR", value_start)?;
                     }
                 }
                 RET if self.stop_char == b'=' => {
-                    params.flags |= match self.hashed_value()? {
-                        FULL if self.stop_char.is_ascii_whitespace() => MAIL_RET_FULL,
-                        HDRS if self.stop_char.is_ascii_whitespace() => MAIL_RET_HDRS,
+                    params.ret = match self.hashed_value()? {
+                        FULL if self.stop_char.is_ascii_whitespace() => Ret::Full,
+                        HDRS if self.stop_char.is_ascii_whitespace() => Ret::Hdrs,
                         _ => {
-                            self.seek_lf()?;
-                            return Err(Error::InvalidParameter { param: "RET" });
+                            self.invalid_parameter("RET", value_start)?;
+                            continue;
                         }
-                    };
+                    }
+                    .into();
                 }
                 ENVID if self.stop_char == b'=' => {
                     let env_id = self.xtext()?;
                     if self.stop_char.is_ascii_whitespace() && (1..=100).contains(&env_id.len()) {
                         params.env_id = env_id.into();
                     } else {
-                        self.seek_lf()?;
-                        return Err(Error::InvalidParameter { param: "ENVID" });
+                        self.invalid_parameter("ENVID", value_start)?;
                     }
                 }
                 REQUIRETLS if self.stop_char.is_ascii_whitespace() => {
-                    params.flags |= MAIL_REQUIRETLS;
+                    params.require_tls = true;
                 }
                 SOLICIT if self.stop_char == b'=' => {
                     let solicit = self.text()?;
                     if !solicit.is_empty() && self.stop_char.is_ascii_whitespace() {
                         params.solicit = solicit.into();
                     } else {
-                        self.seek_lf()?;
-                        return Err(Error::InvalidParameter { param: "SOLICIT" });
+                        self.invalid_parameter("SOLICIT", value_start)?;
                     }
                 }
                 TRANSID if self.stop_char == b'=' => {
@@ -930,8 +1118,7 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
                             continue;
                         }
                     }
-                    self.seek_lf()?;
-                    return Err(Error::InvalidParameter { param: "TRANSID" });
+                    self.invalid_parameter("TRANSID", value_start)?;
                 }
                 MTRK if self.stop_char == b'=' => {
                     let certifier = self.seek_char(b':')?;
@@ -951,8 +1138,7 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
                         }
                         .into();
                     } else {
-                        self.seek_lf()?;
-                        return Err(Error::InvalidParameter { param: "MTRK" });
+                        self.invalid_parameter("MTRK", value_start)?;
                     }
                 }
                 AUTH_ if self.stop_char == b'=' => {
@@ -962,8 +1148,7 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
                     {
                         params.auth = mailbox.into();
                     } else {
-                        self.seek_lf()?;
-                        return Err(Error::InvalidParameter { param: "AUTH" });
+                        self.invalid_parameter("AUTH", value_start)?;
                     }
                 }
                 MT_PRIORITY if self.stop_char == b'=' => {
@@ -971,14 +1156,11 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
                     if priority != i64::MAX && self.stop_char.is_ascii_whitespace() {
                         params.mt_priority = priority;
                     } else {
-                        self.seek_lf()?;
-                        return Err(Error::InvalidParameter {
-                            param: "MT-PRIORITY",
-                        });
+                        self.invalid_parameter("MT-PRIORITY", value_start)?;
                     }
                 }
                 CONPERM if self.stop_char.is_ascii_whitespace() => {
-                    params.flags |= MAIL_CONPERM;
+                    params.conperm = true;
                 }
                 0 => {
                     // hashed_value_long() returned 0, meaning no alphanumeric
@@ -987,10 +1169,11 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
                     // between parameters (harmless). Otherwise it's garbage
                     // like ">", "!", etc. that should be rejected.
                     if !self.stop_char.is_ascii_whitespace() {
-                        self.seek_lf()?;
-                        return Err(Error::SyntaxError {
+                        let context = self.token_context(value_start - 1, LF);
+                        self.parameter_error(Error::SyntaxError {
                             syntax: "MAIL FROM:<reverse-path> [parameters]",
-                        });
+                            context,
+                        })?;
                     }
                 }
                 unknown => {
@@ -1012,10 +1195,11 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
                         }
                     }
 
-                    self.seek_lf()?;
-                    return Err(Error::UnsupportedParameter {
+                    let context = self.token_context(key_start, LF);
+                    self.parameter_error(Error::UnsupportedParameter {
                         param: param.into_string(),
-                    });
+                        context,
+                    })?;
                 }
             }
         }
@@ -1025,55 +1209,68 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
 
     pub fn rcpt_to_parameters(
         &mut self,
-        address: Cow<'y, str>,
-    ) -> Result<RcptTo<Cow<'y, str>>, Error> {
+        address: ForwardPath<Cow<'y, [u8]>>,
+    ) -> Result<RcptTo<Cow<'y, [u8]>>, Error> {
         let mut params = RcptTo {
             address,
+            route: std::mem::take(&mut self.route),
             orcpt: None,
-            rrvs: 0,
-            flags: 0,
+            notify: None,
+            rrvs: None,
+            conneg: false,
         };
-        while self.stop_char != LF {
+        let mut seen = 0u32;
+        'params: while self.stop_char != LF {
             let key = self.hashed_value_long()?;
+            let value_start = self.offset();
+            let key_start = self.key_start(key);
+            let (bit, param) = match key {
+                NOTIFY => (1 << 0, "NOTIFY"),
+                ORCPT => (1 << 1, "ORCPT"),
+                RRVS => (1 << 2, "RRVS"),
+                CONNEG => (1 << 3, "CONNEG"),
+                _ => (0, ""),
+            };
+            if !self.unique_parameter(&mut seen, bit, param, key_start)? {
+                continue;
+            }
             match key {
-                NOTIFY if self.stop_char == b'=' => loop {
-                    match self.hashed_value_long()? {
-                        NEVER
-                            if (params.flags
-                                & (RCPT_NOTIFY_NEVER
-                                    | RCPT_NOTIFY_SUCCESS
-                                    | RCPT_NOTIFY_FAILURE
-                                    | RCPT_NOTIFY_DELAY))
-                                == 0 =>
-                        {
-                            params.flags |= RCPT_NOTIFY_NEVER;
-                        }
-                        SUCCESS => {
-                            params.flags |= RCPT_NOTIFY_SUCCESS;
-                        }
-                        FAILURE => {
-                            params.flags |= RCPT_NOTIFY_FAILURE;
-                        }
-                        DELAY => {
-                            params.flags |= RCPT_NOTIFY_DELAY;
+                NOTIFY if self.stop_char == b'=' => {
+                    let mut notify = NotifySet::default();
+                    let mut never = false;
+                    loop {
+                        match self.hashed_value_long()? {
+                            NEVER if !never && notify.is_never() => {
+                                never = true;
+                            }
+                            SUCCESS if !never => {
+                                notify.success = true;
+                            }
+                            FAILURE if !never => {
+                                notify.failure = true;
+                            }
+                            DELAY if !never => {
+                                notify.delay = true;
+                            }
+                            _ => {
+                                self.invalid_parameter("NOTIFY", value_start)?;
+                                continue 'params;
+                            }
                         }
-                        _ => {
-                            self.seek_lf()?;
-                            return Err(Error::InvalidParameter { param: "NOTIFY" });
+                        if self.stop_char.is_ascii_whitespace() {
+                            break;
+                        } else if self.stop_char != b',' {
+                            self.invalid_parameter("NOTIFY", value_start)?;
+                            continue 'params;
                         }
                     }
-                    if self.stop_char.is_ascii_whitespace() {
-                        break;
-                    } else if self.stop_char != b',' {
-                        self.seek_lf()?;
-                        return Err(Error::InvalidParameter { param: "NOTIFY" });
-                    }
-                },
+                    params.notify = notify.into();
+                }
                 ORCPT if self.stop_char == b'=' => {
                     let v = self.hashed_value()?;
                     if v != RFC822 || self.stop_char != b';' {
-                        self.seek_lf()?;
-                        return Err(Error::InvalidParameter { param: "ORCPT" });
+                        self.invalid_parameter("ORCPT", value_start)?;
+                        continue 'params;
                     }
                     let addr = self.xtext()?;
                     if self.stop_char.is_ascii_whitespace()
@@ -1081,8 +1278,7 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
                     {
                         params.orcpt = addr.into();
                     } else {
-                        self.seek_lf()?;
-                        return Err(Error::InvalidParameter { param: "ORCPT" });
+                        self.invalid_parameter("ORCPT", value_start)?;
                     }
                 }
                 RRVS if self.stop_char == b'=' => {
@@ -1090,35 +1286,33 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
                     if time != i64::MAX && self.stop_char.is_ascii_whitespace()
                         || self.stop_char == b';'
                     {
-                        let is_reject = self.stop_char != b';'
-                            || match self.hashed_value()? {
-                                C if self.stop_char.is_ascii_whitespace() => false,
-                                R if self.stop_char.is_ascii_whitespace() => true,
+                        let on_fail = if self.stop_char != b';' {
+                            RrvsAction::Reject
+                        } else {
+                            match self.hashed_value()? {
+                                C if self.stop_char.is_ascii_whitespace() => RrvsAction::Continue,
+                                R if self.stop_char.is_ascii_whitespace() => RrvsAction::Reject,
                                 _ => {
-                                    self.seek_lf()?;
-                                    return Err(Error::InvalidParameter { param: "RRVS" });
+                                    self.invalid_parameter("RRVS", value_start)?;
+                                    continue 'params;
                                 }
-                            };
-                        params.rrvs = time;
-                        params.flags |= if is_reject {
-                            RCPT_RRVS_REJECT
-                        } else {
-                            RCPT_RRVS_CONTINUE
+                            }
                         };
+                        params.rrvs = Rrvs { time, on_fail }.into();
                     } else {
-                        self.seek_lf()?;
-                        return Err(Error::InvalidParameter { param: "RRVS" });
+                        self.invalid_parameter("RRVS", value_start)?;
                     }
                 }
                 CONNEG if self.stop_char.is_ascii_whitespace() => {
-                    params.flags |= RCPT_CONNEG;
+                    params.conneg = true;
                 }
                 0 => {
                     if !self.stop_char.is_ascii_whitespace() {
-                        self.seek_lf()?;
-                        return Err(Error::SyntaxError {
+                        let context = self.token_context(value_start - 1, LF);
+                        self.parameter_error(Error::SyntaxError {
                             syntax: "RCPT TO:<forward-path> [parameters]",
-                        });
+                            context,
+                        })?;
                     }
                 }
                 unknown => {
@@ -1140,10 +1334,11 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
                         }
                     }
 
-                    self.seek_lf()?;
-                    return Err(Error::UnsupportedParameter {
+                    let context = self.token_context(key_start, LF);
+                    self.parameter_error(Error::UnsupportedParameter {
                         param: param.into_string(),
-                    });
+                        context,
+                    })?;
                 }
             }
         }
@@ -1225,12 +1420,22 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
         })
     }
 
+    /// Adds the domains of a `@a,@b` source route to the current route.
+    fn push_route(&mut self, route: Cow<'y, [u8]>) {
+        match route {
+            Cow::Borrowed(route) => self.route.extend(route_hops(route).map(Cow::Borrowed)),
+            Cow::Owned(route) => self
+                .route
+                .extend(route_hops(&route).map(|hop| hop.to_vec().into())),
+        }
+    }
+
     /// Construct a new `MaybeZeroCopy`, beginning a potentially zero-copy read from the input.
     #[inline(always)]
     fn start_zero_copy(&self) -> MaybeZeroCopy<'y> {
         MaybeZeroCopy {
             remaining: self.bytes.as_slice(),
-            data: Cow::Borrowed(""),
+            data: Cow::Borrowed(&[][..]),
         }
     }
 
@@ -1252,8 +1457,8 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
     /// Get the first byte of a `MaybeZeroCopy`.
     fn first_excluding_current(&self, out: &MaybeZeroCopy<'y>) -> Option<u8> {
         out.data
-            .bytes()
-            .next()
+            .first()
+            .copied()
             .or_else(|| self.extra_before_current(out).first().copied())
     }
 
@@ -1269,23 +1474,9 @@ impl<'x, 'y> Rfc5321Parser<'x, 'y> {
         self.drop_extra(out);
 
         if out.data.is_empty() {
-            out.data = String::from_utf8_lossy(extra);
+            out.data = Cow::Borrowed(extra);
         } else {
-            if let Cow::Borrowed(s) = out.data {
-                let mut buf = String::with_capacity(32);
-                buf.push_str(s);
-                out.data = Cow::Owned(buf);
-            }
-
-            // The same algorithm as `String::from_utf8_lossy`, but appending to an existing
-            // buffer.
-            let out = out.data.to_mut();
-            for chunk in extra.utf8_chunks() {
-                out.push_str(chunk.valid());
-                if !chunk.invalid().is_empty() {
-                    out.push(char::REPLACEMENT_CHARACTER);
-                }
-            }
+            out.data.to_mut().extend_from_slice(extra);
         }
     }
 }
@@ -1295,7 +1486,25 @@ struct MaybeZeroCopy<'y> {
     /// Slice from where we start parsing to the end of the input.
     remaining: &'y [u8],
     /// The data itself.
-    data: Cow<'y, str>,
+    data: Cow<'y, [u8]>,
+}
+
+fn route_hops(route: &[u8]) -> impl Iterator<Item = &[u8]> {
+    route
+        .split(|&ch| ch == b',')
+        .map(|hop| hop.strip_prefix(b"@").unwrap_or(hop))
+        .filter(|hop| !hop.is_empty())
+}
+
+/// Decodes a parsed value as UTF-8, replacing invalid sequences with U+FFFD.
+pub(crate) fn into_utf8_lossy(value: Cow<'_, [u8]>) -> Cow<'_, str> {
+    match value {
+        Cow::Borrowed(value) => String::from_utf8_lossy(value),
+        Cow::Owned(value) => match String::from_utf8(value) {
+            Ok(value) => Cow::Owned(value),
+            Err(err) => Cow::Owned(String::from_utf8_lossy(err.as_bytes()).into_owned()),
+        },
+    }
 }
 
 #[cfg(test)]
@@ -1318,6 +1527,7 @@ mod tests {
                 "EHLO",
                 Err(Error::SyntaxError {
                     syntax: "EHLO domain",
+                    context: context("EHLO", 4, 0),
                 }),
             ),
             (
@@ -1330,6 +1540,7 @@ mod tests {
                 "HELO",
                 Err(Error::SyntaxError {
                     syntax: "HELO domain",
+                    context: context("HELO", 4, 0),
                 }),
             ),
             (
@@ -1342,6 +1553,7 @@ mod tests {
                 "LHLO",
                 Err(Error::SyntaxError {
                     syntax: "LHLO domain",
+                    context: context("LHLO", 4, 0),
                 }),
             ),
             // VRFY
@@ -1361,12 +1573,14 @@ mod tests {
                 "VRFY \"\"",
                 Err(Error::SyntaxError {
                     syntax: "VRFY string",
+                    context: context("VRFY", 5, 2),
                 }),
             ),
             (
                 "VRFY",
                 Err(Error::SyntaxError {
                     syntax: "VRFY string",
+                    context: context("VRFY", 4, 0),
                 }),
             ),
             // EXPN
@@ -1386,12 +1600,14 @@ mod tests {
                 "EXPN \"\"",
                 Err(Error::SyntaxError {
                     syntax: "EXPN string",
+                    context: context("EXPN", 5, 2),
                 }),
             ),
             (
                 "EXPN",
                 Err(Error::SyntaxError {
                     syntax: "EXPN string",
+                    context: context("EXPN", 4, 0),
                 }),
             ),
             // NOOP
@@ -1441,24 +1657,28 @@ mod tests {
                 "BDAT",
                 Err(Error::SyntaxError {
                     syntax: "BDAT chunk-size [LAST]",
+                    context: context("BDAT", 4, 0),
                 }),
             ),
             (
                 "BDAT 123LAST",
                 Err(Error::SyntaxError {
                     syntax: "BDAT chunk-size [LAST]",
+                    context: context("BDAT", 5, 7),
                 }),
             ),
             (
                 "BDAT 123x LAST",
                 Err(Error::SyntaxError {
                     syntax: "BDAT chunk-size [LAST]",
+                    context: context("BDAT", 5, 9),
                 }),
             ),
             (
                 "BDAT LAST",
                 Err(Error::SyntaxError {
                     syntax: "BDAT chunk-size [LAST]",
+                    context: context("BDAT", 5, 4),
                 }),
             ),
             // AUTH
@@ -1494,6 +1714,7 @@ mod tests {
                 "AUTH",
                 Err(Error::SyntaxError {
                     syntax: "AUTH mechanism [initial-response]",
+                    context: context("AUTH", 4, 0),
                 }),
             ),
             // ETRN
@@ -1513,12 +1734,14 @@ mod tests {
                 "ETRN \"\"",
                 Err(Error::SyntaxError {
                     syntax: "ETRN name",
+                    context: context("ETRN", 5, 2),
                 }),
             ),
             (
                 "ETRN",
                 Err(Error::SyntaxError {
                     syntax: "ETRN name",
+                    context: context("ETRN", 4, 0),
                 }),
             ),
             // ATRN
@@ -1552,6 +1775,7 @@ mod tests {
                 "ATRN",
                 Err(Error::SyntaxError {
                     syntax: "ATRN domain[,domain]",
+                    context: context("ATRN", 4, 0),
                 }),
             ),
             // BURL
@@ -1582,6 +1806,7 @@ mod tests {
                 "BURL",
                 Err(Error::SyntaxError {
                     syntax: "BURL absolute-uri [LAST]",
+                    context: context("BURL", 4, 0),
                 }),
             ),
             // MAIL FROM
@@ -1594,7 +1819,11 @@ mod tests {
             (
                 "MAIL FROM:<@a,@b:user@d>",
                 Ok(Request::Mail {
-                    from: "user@d".into(),
+                    from: MailFrom {
+                        address: ReversePath::Mailbox("user@d".into()),
+                        route: vec!["a".into(), "b".into()],
+                        ..Default::default()
+                    },
                 }),
             ),
             (
@@ -1612,7 +1841,9 @@ mod tests {
             ("MAIL  FROM : <>", Ok(Request::Mail { from: "".into() })),
             (
                 "MAIL  FROM : <postmaster>",
-                Ok(Request::Mail { from: "".into() }),
+                Ok(Request::Mail {
+                    from: "postmaster".into(),
+                }),
             ),
             ("MAIL  FROM : < >", Ok(Request::Mail { from: "".into() })),
             (
@@ -1621,29 +1852,54 @@ mod tests {
                     from: "hi.there@valid.org".into(),
                 }),
             ),
-            ("MAIL FROM:<@invalid>", Err(Error::InvalidSenderAddress)),
+            (
+                "MAIL FROM:<@invalid>",
+                Err(Error::InvalidSenderAddress {
+                    context: context("MAIL", 11, 8),
+                }),
+            ),
             (
                 "MAIL FROM:<hi@@invalid.org>",
-                Err(Error::InvalidSenderAddress),
+                Err(Error::InvalidSenderAddress {
+                    context: context("MAIL", 11, 15),
+                }),
             ),
             (
                 "MAIL FROM:<hi..there@invalid.org>",
-                Err(Error::InvalidSenderAddress),
+                Err(Error::InvalidSenderAddress {
+                    context: context("MAIL", 11, 21),
+                }),
             ),
             (
                 "MAIL FROM:<hi.there@invalid..org>",
-                Err(Error::InvalidSenderAddress),
+                Err(Error::InvalidSenderAddress {
+                    context: context("MAIL", 11, 21),
+                }),
             ),
             (
                 "MAIL FROM:<hi.there@.invalid.org>",
-                Err(Error::InvalidSenderAddress),
+                Err(Error::InvalidSenderAddress {
+                    context: context("MAIL", 11, 21),
+                }),
             ),
             (
                 "MAIL FROM:<.hi.there@invalid.org>",
-                Err(Error::InvalidSenderAddress),
+                Err(Error::InvalidSenderAddress {
+                    context: context("MAIL", 11, 21),
+                }),
+            ),
+            (
+                "MAIL FROM:<@>",
+                Err(Error::InvalidSenderAddress {
+                    context: context("MAIL", 11, 1),
+                }),
+            ),
+            (
+                "MAIL FROM:<.@.>",
+                Err(Error::InvalidSenderAddress {
+                    context: context("MAIL", 11, 3),
+                }),
             ),
-            ("MAIL FROM:<@>", Err(Error::InvalidSenderAddress)),
-            ("MAIL FROM:<.@.>", Err(Error::InvalidSenderAddress)),
             (
                 "RCPT TO:<孫子@áéíóú.org>",
                 Ok(Request::Rcpt {
@@ -1657,7 +1913,37 @@ mod tests {
                     to: "Jones@XYZ.COM".into(),
                 }),
             ),
-            ("RCPT TO:<>", Ok(Request::Rcpt { to: "".into() })),
+            (
+                "RCPT TO:<>",
+                Err(Error::InvalidRecipientAddress {
+                    context: context("RCPT", 9, 0),
+                }),
+            ),
+            (
+                "RCPT TO:<Postmaster>",
+                Ok(Request::Rcpt {
+                    to: RcptTo {
+                        address: ForwardPath::Postmaster,
+                        ..Default::default()
+                    },
+                }),
+            ),
+            (
+                "RCPT TO:<@hosta.int,@jkl.org:userc@d.bar.org>",
+                Ok(Request::Rcpt {
+                    to: RcptTo {
+                        address: ForwardPath::Mailbox("userc@d.bar.org".into()),
+                        route: vec!["hosta.int".into(), "jkl.org".into()],
+                        ..Default::default()
+                    },
+                }),
+            ),
+            (
+                "RCPT TO:<postmaster@example.org>",
+                Ok(Request::Rcpt {
+                    to: "postmaster@example.org".into(),
+                }),
+            ),
             // Invalid commands
             ("", Err(Error::UnknownCommand)),
             ("X-SPECIAL", Err(Error::UnknownCommand)),
@@ -1667,24 +1953,28 @@ mod tests {
                 "MAIL FROM:<> HELLO=WORLD",
                 Err(Error::UnsupportedParameter {
                     param: "HELLO=WORLD".to_string(),
+                    context: context("MAIL", 13, 11),
                 }),
             ),
             (
                 "MAIL FROM:<> VERY_LONG_AND_INVALID=PARAM",
                 Err(Error::UnsupportedParameter {
                     param: "VERY_LONG_AND_INVALID=PARAM".to_string(),
+                    context: context("MAIL", 13, 27),
                 }),
             ),
             (
                 "MAIL FROM:<> SMTPUTF8=YES",
                 Err(Error::UnsupportedParameter {
                     param: "SMTPUTF8=YES".to_string(),
+                    context: context("MAIL", 13, 12),
                 }),
             ),
             (
                 "MAIL FROM:<> SMTPUTF8=YES",
                 Err(Error::UnsupportedParameter {
                     param: "SMTPUTF8=YES".to_string(),
+                    context: context("MAIL", 13, 12),
                 }),
             ),
             // Garbage after address path (non-alphanumeric chars that don't
@@ -1694,18 +1984,21 @@ mod tests {
                 "MAIL FROM:<>>>>>",
                 Err(Error::SyntaxError {
                     syntax: "MAIL FROM:<reverse-path> [parameters]",
+                    context: context("MAIL", 12, 4),
                 }),
             ),
             (
                 "MAIL FROM:<a@b.com>>>",
                 Err(Error::SyntaxError {
                     syntax: "MAIL FROM:<reverse-path> [parameters]",
+                    context: context("MAIL", 19, 2),
                 }),
             ),
             (
-                "RCPT TO:<>>>>>",
+                "RCPT TO:<a@b.org>>>>>",
                 Err(Error::SyntaxError {
                     syntax: "RCPT TO:<forward-path> [parameters]",
+                    context: context("RCPT", 17, 4),
                 }),
             ),
             // Parameters
@@ -1714,7 +2007,7 @@ mod tests {
                 Ok(Request::Mail {
                     from: MailFrom {
                         address: "".into(),
-                        flags: MAIL_SMTPUTF8,
+                        smtp_utf8: true,
                         ..Default::default()
                     },
                 }),
@@ -1724,34 +2017,79 @@ mod tests {
                 Ok(Request::Mail {
                     from: MailFrom {
                         address: "".into(),
-                        flags: MAIL_SMTPUTF8 | MAIL_REQUIRETLS | MAIL_CONPERM,
+                        smtp_utf8: true,
+                        require_tls: true,
+                        conperm: true,
                         ..Default::default()
                     },
                 }),
             ),
             (
-                "RCPT TO:<> CONNEG",
+                "RCPT TO:<a@b.org> CONNEG",
                 Ok(Request::Rcpt {
                     to: RcptTo {
+                        address: "a@b.org".into(),
+                        conneg: true,
+                        ..Default::default()
+                    },
+                }),
+            ),
+            (
+                "MAIL FROM:<> BODY=BINARYMIME RET=FULL",
+                Ok(Request::Mail {
+                    from: MailFrom {
                         address: "".into(),
-                        flags: RCPT_CONNEG,
+                        body: Some(Body::BinaryMime),
+                        ret: Some(Ret::Full),
                         ..Default::default()
                     },
                 }),
             ),
             (
-                "MAIL FROM:<> BODY=BINARYMIME BODY=7BIT BODY=8BITMIME",
+                "MAIL FROM:<> BODY=7bit RET=hdrs",
                 Ok(Request::Mail {
                     from: MailFrom {
                         address: "".into(),
-                        flags: MAIL_BODY_7BIT | MAIL_BODY_8BITMIME | MAIL_BODY_BINARYMIME,
+                        body: Some(Body::SevenBit),
+                        ret: Some(Ret::Hdrs),
                         ..Default::default()
                     },
                 }),
             ),
+            (
+                "MAIL FROM:<> BODY=BINARYMIME BODY=7BIT BODY=8BITMIME",
+                Err(Error::DuplicateParameter {
+                    param: "BODY",
+                    context: context("MAIL", 29, 9),
+                }),
+            ),
+            (
+                "MAIL FROM:<> SIZE=10 SIZE=999999999",
+                Err(Error::DuplicateParameter {
+                    param: "SIZE",
+                    context: context("MAIL", 21, 14),
+                }),
+            ),
+            (
+                "MAIL FROM:<> HOLDFOR=10 HOLDFOR=1000",
+                Err(Error::DuplicateParameter {
+                    param: "HOLDFOR",
+                    context: context("MAIL", 24, 12),
+                }),
+            ),
+            (
+                "MAIL FROM:<> SMTPUTF8 SMTPUTF8",
+                Err(Error::DuplicateParameter {
+                    param: "SMTPUTF8",
+                    context: context("MAIL", 22, 8),
+                }),
+            ),
             (
                 "MAIL FROM:<> BODY=OTHER",
-                Err(Error::InvalidParameter { param: "BODY" }),
+                Err(Error::InvalidParameter {
+                    param: "BODY",
+                    context: context("MAIL", 18, 5),
+                }),
             ),
             (
                 "MAIL FROM:<> SIZE=500000",
@@ -1765,23 +2103,35 @@ mod tests {
             ),
             (
                 "MAIL FROM:<> SIZE=ABC",
-                Err(Error::InvalidParameter { param: "SIZE" }),
+                Err(Error::InvalidParameter {
+                    param: "SIZE",
+                    context: context("MAIL", 18, 3),
+                }),
             ),
             (
                 "MAIL FROM:<> SIZE=-100",
-                Err(Error::InvalidParameter { param: "SIZE" }),
+                Err(Error::InvalidParameter {
+                    param: "SIZE",
+                    context: context("MAIL", 18, 4),
+                }),
             ),
             (
                 "MAIL FROM:<> SIZE=",
-                Err(Error::InvalidParameter { param: "SIZE" }),
+                Err(Error::InvalidParameter {
+                    param: "SIZE",
+                    context: context("MAIL", 18, 0),
+                }),
             ),
             (
                 "MAIL FROM:<> BY=120;R",
                 Ok(Request::Mail {
                     from: MailFrom {
                         address: "".into(),
-                        by: 120,
-                        flags: MAIL_BY_RETURN,
+                        by: Some(DeliverBy {
+                            time: 120,
+                            mode: DeliverByMode::Return,
+                            trace: false,
+                        }),
                         ..Default::default()
                     },
                 }),
@@ -1791,8 +2141,11 @@ mod tests {
                 Ok(Request::Mail {
                     from: MailFrom {
                         address: "".into(),
-                        by: 0,
-                        flags: MAIL_BY_NOTIFY,
+                        by: Some(DeliverBy {
+                            time: 0,
+                            mode: DeliverByMode::Notify,
+                            trace: false,
+                        }),
                         ..Default::default()
                     },
                 }),
@@ -1802,8 +2155,11 @@ mod tests {
                 Ok(Request::Mail {
                     from: MailFrom {
                         address: "".into(),
-                        by: -10,
-                        flags: MAIL_BY_RETURN | MAIL_BY_TRACE,
+                        by: Some(DeliverBy {
+                            time: -10,
+                            mode: DeliverByMode::Return,
+                            trace: true,
+                        }),
                         ..Default::default()
                     },
                 }),
@@ -1813,43 +2169,70 @@ mod tests {
                 Ok(Request::Mail {
                     from: MailFrom {
                         address: "".into(),
-                        by: 22,
-                        flags: MAIL_BY_NOTIFY | MAIL_BY_TRACE,
+                        by: Some(DeliverBy {
+                            time: 22,
+                            mode: DeliverByMode::Notify,
+                            trace: true,
+                        }),
                         ..Default::default()
                     },
                 }),
             ),
             (
                 "MAIL FROM:<> BY=120",
-                Err(Error::InvalidParameter { param: "BY" }),
+                Err(Error::InvalidParameter {
+                    param: "BY",
+                    context: context("MAIL", 16, 3),
+                }),
             ),
             (
                 "MAIL FROM:<> BY=120;T",
-                Err(Error::InvalidParameter { param: "BY" }),
+                Err(Error::InvalidParameter {
+                    param: "BY",
+                    context: context("MAIL", 16, 5),
+                }),
             ),
             (
                 "MAIL FROM:<> BY=120;",
-                Err(Error::InvalidParameter { param: "BY" }),
+                Err(Error::InvalidParameter {
+                    param: "BY",
+                    context: context("MAIL", 16, 4),
+                }),
             ),
             (
                 "MAIL FROM:<> BY=120;0",
-                Err(Error::InvalidParameter { param: "BY" }),
+                Err(Error::InvalidParameter {
+                    param: "BY",
+                    context: context("MAIL", 16, 5),
+                }),
             ),
             (
                 "MAIL FROM:<> BY=120;;",
-                Err(Error::InvalidParameter { param: "BY" }),
+                Err(Error::InvalidParameter {
+                    param: "BY",
+                    context: context("MAIL", 16, 5),
+                }),
             ),
             (
                 "MAIL FROM:<> BY=;",
-                Err(Error::InvalidParameter { param: "BY" }),
+                Err(Error::InvalidParameter {
+                    param: "BY",
+                    context: context("MAIL", 16, 1),
+                }),
             ),
             (
                 "MAIL FROM:<> BY=;R",
-                Err(Error::InvalidParameter { param: "BY" }),
+                Err(Error::InvalidParameter {
+                    param: "BY",
+                    context: context("MAIL", 16, 2),
+                }),
             ),
             (
                 "MAIL FROM:<> BY=",
-                Err(Error::InvalidParameter { param: "BY" }),
+                Err(Error::InvalidParameter {
+                    param: "BY",
+                    context: context("MAIL", 16, 0),
+                }),
             ),
             (
                 "MAIL FROM:<> HOLDUNTIL=12345 HOLDFOR=67890",
@@ -1864,125 +2247,189 @@ mod tests {
             ),
             (
                 "MAIL FROM:<> HOLDUNTIL=0ABC",
-                Err(Error::InvalidParameter { param: "HOLDUNTIL" }),
+                Err(Error::InvalidParameter {
+                    param: "HOLDUNTIL",
+                    context: context("MAIL", 23, 4),
+                }),
             ),
             (
                 "MAIL FROM:<> HOLDUNTIL=",
-                Err(Error::InvalidParameter { param: "HOLDUNTIL" }),
+                Err(Error::InvalidParameter {
+                    param: "HOLDUNTIL",
+                    context: context("MAIL", 23, 0),
+                }),
             ),
             (
                 "MAIL FROM:<> HOLDFOR=XYZ",
-                Err(Error::InvalidParameter { param: "HOLDFOR" }),
+                Err(Error::InvalidParameter {
+                    param: "HOLDFOR",
+                    context: context("MAIL", 21, 3),
+                }),
             ),
             (
                 "MAIL FROM:<> HOLDFOR=",
-                Err(Error::InvalidParameter { param: "HOLDFOR" }),
+                Err(Error::InvalidParameter {
+                    param: "HOLDFOR",
+                    context: context("MAIL", 21, 0),
+                }),
             ),
             (
-                "RCPT TO:<> NOTIFY=FAILURE",
+                "RCPT TO:<a@b.org> NOTIFY=FAILURE",
                 Ok(Request::Rcpt {
                     to: RcptTo {
-                        address: "".into(),
-                        flags: RCPT_NOTIFY_FAILURE,
+                        address: "a@b.org".into(),
+                        notify: Some(NotifySet {
+                            failure: true,
+                            ..Default::default()
+                        }),
                         ..Default::default()
                     },
                 }),
             ),
             (
-                "RCPT TO:<> NOTIFY=FAILURE,DELAY",
+                "RCPT TO:<a@b.org> NOTIFY=FAILURE,DELAY",
                 Ok(Request::Rcpt {
                     to: RcptTo {
-                        address: "".into(),
-                        flags: RCPT_NOTIFY_FAILURE | RCPT_NOTIFY_DELAY,
+                        address: "a@b.org".into(),
+                        notify: Some(NotifySet {
+                            failure: true,
+                            delay: true,
+                            ..Default::default()
+                        }),
                         ..Default::default()
                     },
                 }),
             ),
             (
-                "RCPT TO:<> NOTIFY=SUCCESS,FAILURE,DELAY",
+                "RCPT TO:<a@b.org> NOTIFY=SUCCESS,FAILURE,DELAY",
                 Ok(Request::Rcpt {
                     to: RcptTo {
-                        address: "".into(),
-                        flags: RCPT_NOTIFY_FAILURE | RCPT_NOTIFY_DELAY | RCPT_NOTIFY_SUCCESS,
+                        address: "a@b.org".into(),
+                        notify: Some(NotifySet {
+                            success: true,
+                            failure: true,
+                            delay: true,
+                        }),
                         ..Default::default()
                     },
                 }),
             ),
             (
-                "RCPT TO:<> NOTIFY=NEVER",
+                "RCPT TO:<a@b.org> NOTIFY=NEVER",
                 Ok(Request::Rcpt {
                     to: RcptTo {
-                        address: "".into(),
-                        flags: RCPT_NOTIFY_NEVER,
+                        address: "a@b.org".into(),
+                        notify: Some(NotifySet::NEVER),
                         ..Default::default()
                     },
                 }),
             ),
             (
-                "RCPT TO:<> NOTIFY=",
-                Err(Error::InvalidParameter { param: "NOTIFY" }),
+                "RCPT TO:<a@b.org> NOTIFY=",
+                Err(Error::InvalidParameter {
+                    param: "NOTIFY",
+                    context: context("RCPT", 25, 0),
+                }),
+            ),
+            (
+                "RCPT TO:<a@b.org> NOTIFY=FAILURE,NEVER",
+                Err(Error::InvalidParameter {
+                    param: "NOTIFY",
+                    context: context("RCPT", 25, 13),
+                }),
             ),
             (
-                "RCPT TO:<> NOTIFY=FAILURE,NEVER",
-                Err(Error::InvalidParameter { param: "NOTIFY" }),
+                "RCPT TO:<a@b.org> NOTIFY=NEVER,SUCCESS",
+                Err(Error::InvalidParameter {
+                    param: "NOTIFY",
+                    context: context("RCPT", 25, 13),
+                }),
+            ),
+            (
+                "RCPT TO:<a@b.org> NOTIFY=DELAY NOTIFY=NEVER",
+                Err(Error::DuplicateParameter {
+                    param: "NOTIFY",
+                    context: context("RCPT", 31, 12),
+                }),
             ),
             (
-                "RCPT TO:<> NOTIFY=CHIMICHANGA",
-                Err(Error::InvalidParameter { param: "NOTIFY" }),
+                "RCPT TO:<a@b.org> NOTIFY=CHIMICHANGA",
+                Err(Error::InvalidParameter {
+                    param: "NOTIFY",
+                    context: context("RCPT", 25, 11),
+                }),
             ),
             (
-                "RCPT TO:<> ORCPT=rfc822;Bob@Example.COM",
+                "RCPT TO:<a@b.org> ORCPT=rfc822;Bob@Example.COM",
                 Ok(Request::Rcpt {
                     to: RcptTo {
-                        address: "".into(),
+                        address: "a@b.org".into(),
                         orcpt: Some("Bob@Example.COM".into()),
                         ..Default::default()
                     },
                 }),
             ),
             (
-                concat!("RCPT TO:<> ", "ORCPT=rfc822;George+20@Tax-+20ME+20.GOV"),
+                concat!(
+                    "RCPT TO:<a@b.org> ",
+                    "ORCPT=rfc822;George+20@Tax-+20ME+20.GOV"
+                ),
                 Ok(Request::Rcpt {
                     to: RcptTo {
-                        address: "".into(),
+                        address: "a@b.org".into(),
                         orcpt: Some("George @Tax- ME .GOV".into()),
                         ..Default::default()
                     },
                 }),
             ),
             (
-                "RCPT TO:<> ORCPT=",
-                Err(Error::InvalidParameter { param: "ORCPT" }),
+                "RCPT TO:<a@b.org> ORCPT=",
+                Err(Error::InvalidParameter {
+                    param: "ORCPT",
+                    context: context("RCPT", 24, 0),
+                }),
             ),
             (
-                "RCPT TO:<> ORCPT=;hello@domain.org",
-                Err(Error::InvalidParameter { param: "ORCPT" }),
+                "RCPT TO:<a@b.org> ORCPT=;hello@domain.org",
+                Err(Error::InvalidParameter {
+                    param: "ORCPT",
+                    context: context("RCPT", 24, 17),
+                }),
             ),
             (
-                "RCPT TO:<> ORCPT=rfc822;",
-                Err(Error::InvalidParameter { param: "ORCPT" }),
+                "RCPT TO:<a@b.org> ORCPT=rfc822;",
+                Err(Error::InvalidParameter {
+                    param: "ORCPT",
+                    context: context("RCPT", 24, 7),
+                }),
             ),
             (
-                "RCPT TO:<> ORCPT=;",
-                Err(Error::InvalidParameter { param: "ORCPT" }),
+                "RCPT TO:<a@b.org> ORCPT=;",
+                Err(Error::InvalidParameter {
+                    param: "ORCPT",
+                    context: context("RCPT", 24, 1),
+                }),
             ),
             (
                 "MAIL FROM:<> RET=HDRS RET=FULL",
-                Ok(Request::Mail {
-                    from: MailFrom {
-                        address: "".into(),
-                        flags: MAIL_RET_FULL | MAIL_RET_HDRS,
-                        ..Default::default()
-                    },
+                Err(Error::DuplicateParameter {
+                    param: "RET",
+                    context: context("MAIL", 22, 8),
                 }),
             ),
             (
                 "MAIL FROM:<> RET=",
-                Err(Error::InvalidParameter { param: "RET" }),
+                Err(Error::InvalidParameter {
+                    param: "RET",
+                    context: context("MAIL", 17, 0),
+                }),
             ),
             (
                 "MAIL FROM:<> RET=ENCHILADA",
-                Err(Error::InvalidParameter { param: "RET" }),
+                Err(Error::InvalidParameter {
+                    param: "RET",
+                    context: context("MAIL", 17, 9),
+                }),
             ),
             (
                 "MAIL FROM:<> ENVID=QQ314159",
@@ -2006,7 +2453,10 @@ mod tests {
             ),
             (
                 "MAIL FROM:<> ENVID=",
-                Err(Error::InvalidParameter { param: "ENVID" }),
+                Err(Error::InvalidParameter {
+                    param: "ENVID",
+                    context: context("MAIL", 19, 0),
+                }),
             ),
             (
                 "MAIL FROM:<> SOLICIT=org.example:ADV:ADLT",
@@ -2033,7 +2483,10 @@ mod tests {
             ),
             (
                 "MAIL FROM:<> SOLICIT=",
-                Err(Error::InvalidParameter { param: "SOLICIT" }),
+                Err(Error::InvalidParameter {
+                    param: "SOLICIT",
+                    context: context("MAIL", 21, 0),
+                }),
             ),
             (
                 "MAIL FROM:<> TRANSID=<12345@claremont.edu>",
@@ -2047,7 +2500,10 @@ mod tests {
             ),
             (
                 "MAIL FROM:<> TRANSID=",
-                Err(Error::InvalidParameter { param: "TRANSID" }),
+                Err(Error::InvalidParameter {
+                    param: "TRANSID",
+                    context: context("MAIL", 21, 0),
+                }),
             ),
             (
                 "MAIL FROM:<> MTRK=my-ceritifier",
@@ -2079,23 +2535,38 @@ mod tests {
             ),
             (
                 "MAIL FROM:<> MTRK=",
-                Err(Error::InvalidParameter { param: "MTRK" }),
+                Err(Error::InvalidParameter {
+                    param: "MTRK",
+                    context: context("MAIL", 18, 0),
+                }),
             ),
             (
                 "MAIL FROM:<> MTRK=:",
-                Err(Error::InvalidParameter { param: "MTRK" }),
+                Err(Error::InvalidParameter {
+                    param: "MTRK",
+                    context: context("MAIL", 18, 1),
+                }),
             ),
             (
                 "MAIL FROM:<> MTRK=:998",
-                Err(Error::InvalidParameter { param: "MTRK" }),
+                Err(Error::InvalidParameter {
+                    param: "MTRK",
+                    context: context("MAIL", 18, 4),
+                }),
             ),
             (
                 "MAIL FROM:<> MTRK=abc:",
-                Err(Error::InvalidParameter { param: "MTRK" }),
+                Err(Error::InvalidParameter {
+                    param: "MTRK",
+                    context: context("MAIL", 18, 4),
+                }),
             ),
             (
                 "MAIL FROM:<> MTRK=abc:abc",
-                Err(Error::InvalidParameter { param: "MTRK" }),
+                Err(Error::InvalidParameter {
+                    param: "MTRK",
+                    context: context("MAIL", 18, 7),
+                }),
             ),
             (
                 "MAIL FROM:<> AUTH=<>",
@@ -2119,7 +2590,10 @@ mod tests {
             ),
             (
                 "MAIL FROM:<> AUTH=",
-                Err(Error::InvalidParameter { param: "AUTH" }),
+                Err(Error::InvalidParameter {
+                    param: "AUTH",
+                    context: context("MAIL", 18, 0),
+                }),
             ),
             (
                 "MAIL FROM:<> MT-PRIORITY=3",
@@ -2145,77 +2619,102 @@ mod tests {
                 "MAIL FROM:<> MT-PRIORITY=",
                 Err(Error::InvalidParameter {
                     param: "MT-PRIORITY",
+                    context: context("MAIL", 25, 0),
                 }),
             ),
             (
                 "MAIL FROM:<> MT-PRIORITY=ab",
                 Err(Error::InvalidParameter {
                     param: "MT-PRIORITY",
+                    context: context("MAIL", 25, 2),
                 }),
             ),
             (
                 "MAIL FROM:<> MT-PRIORITY=-",
                 Err(Error::InvalidParameter {
                     param: "MT-PRIORITY",
+                    context: context("MAIL", 25, 1),
                 }),
             ),
             (
-                "RCPT TO:<> RRVS=2014-04-03T23:01:00Z",
+                "RCPT TO:<a@b.org> RRVS=2014-04-03T23:01:00Z",
                 Ok(Request::Rcpt {
                     to: RcptTo {
-                        address: "".into(),
-                        rrvs: 1396566060,
-                        flags: RCPT_RRVS_REJECT,
+                        address: "a@b.org".into(),
+                        rrvs: Some(Rrvs {
+                            time: 1396566060,
+                            on_fail: RrvsAction::Reject,
+                        }),
                         ..Default::default()
                     },
                 }),
             ),
             (
-                "RCPT TO:<> RRVS=1997-11-24T14:22:01-08:00;C",
+                "RCPT TO:<a@b.org> RRVS=1997-11-24T14:22:01-08:00;C",
                 Ok(Request::Rcpt {
                     to: RcptTo {
-                        address: "".into(),
-                        rrvs: 880410121,
-                        flags: RCPT_RRVS_CONTINUE,
+                        address: "a@b.org".into(),
+                        rrvs: Some(Rrvs {
+                            time: 880410121,
+                            on_fail: RrvsAction::Continue,
+                        }),
                         ..Default::default()
                     },
                 }),
             ),
             (
-                "RCPT TO:<> RRVS=2003-07-01T10:52:37+02:00;R",
+                "RCPT TO:<a@b.org> RRVS=2003-07-01T10:52:37+02:00;R",
                 Ok(Request::Rcpt {
                     to: RcptTo {
-                        address: "".into(),
-                        rrvs: 1057049557,
-                        flags: RCPT_RRVS_REJECT,
+                        address: "a@b.org".into(),
+                        rrvs: Some(Rrvs {
+                            time: 1057049557,
+                            on_fail: RrvsAction::Reject,
+                        }),
                         ..Default::default()
                     },
                 }),
             ),
             (
-                "RCPT TO:<> RRVS=",
-                Err(Error::InvalidParameter { param: "RRVS" }),
+                "RCPT TO:<a@b.org> RRVS=",
+                Err(Error::InvalidParameter {
+                    param: "RRVS",
+                    context: context("RCPT", 23, 0),
+                }),
             ),
             (
-                "RCPT TO:<> RRVS=2022-01-02",
-                Err(Error::InvalidParameter { param: "RRVS" }),
+                "RCPT TO:<a@b.org> RRVS=2022-01-02",
+                Err(Error::InvalidParameter {
+                    param: "RRVS",
+                    context: context("RCPT", 23, 10),
+                }),
             ),
             (
-                "RCPT TO:<> RRVS=2022-01-02T01:01:01",
-                Err(Error::InvalidParameter { param: "RRVS" }),
+                "RCPT TO:<a@b.org> RRVS=2022-01-02T01:01:01",
+                Err(Error::InvalidParameter {
+                    param: "RRVS",
+                    context: context("RCPT", 23, 19),
+                }),
             ),
             (
-                "RCPT TO:<> RRVS=2022-01-02T01:01:01ZZ",
-                Err(Error::InvalidParameter { param: "RRVS" }),
+                "RCPT TO:<a@b.org> RRVS=2022-01-02T01:01:01ZZ",
+                Err(Error::InvalidParameter {
+                    param: "RRVS",
+                    context: context("RCPT", 23, 21),
+                }),
             ),
             (
-                "RCPT TO:<> RRVS=ABC",
-                Err(Error::InvalidParameter { param: "RRVS" }),
+                "RCPT TO:<a@b.org> RRVS=ABC",
+                Err(Error::InvalidParameter {
+                    param: "RRVS",
+                    context: context("RCPT", 23, 3),
+                }),
             ),
             (
                 r#"MAIL FROM:<foo@bar"aa\\" hello world".com>"#,
                 Err(Error::SyntaxError {
                     syntax: "MAIL FROM:<reverse-path> [parameters]",
+                    context: context("MAIL", 5, 37),
                 }),
             ),
             (
@@ -2238,6 +2737,200 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_request_raw() {
+        for (request, raw, lossy) in [
+            (
+                &b"MAIL FROM:<j\xe9@example.org>\r\n"[..],
+                Request::Mail {
+                    from: MailFrom {
+                        address: ReversePath::Mailbox(Cow::Borrowed(&b"j\xe9@example.org"[..])),
+                        ..Default::default()
+                    },
+                },
+                Request::Mail {
+                    from: "j\u{fffd}@example.org".into(),
+                },
+            ),
+            (
+                &b"RCPT TO:<\"j\\\xe9\"@example.org> ORCPT=rfc822;j+E9@example.org\n"[..],
+                Request::Rcpt {
+                    to: RcptTo {
+                        address: ForwardPath::Mailbox(Cow::Owned(b"j\xe9@example.org".to_vec())),
+                        orcpt: Some(Cow::Owned(b"j\xe9@example.org".to_vec())),
+                        ..Default::default()
+                    },
+                },
+                Request::Rcpt {
+                    to: RcptTo {
+                        address: "j\u{fffd}@example.org".into(),
+                        orcpt: Some("j\u{fffd}@example.org".into()),
+                        ..Default::default()
+                    },
+                },
+            ),
+            (
+                "EHLO \u{4f8b}\u{5b50}.\u{5e7f}\u{544a}\r\n".as_bytes(),
+                Request::Ehlo {
+                    host: Cow::Borrowed("\u{4f8b}\u{5b50}.\u{5e7f}\u{544a}".as_bytes()),
+                },
+                Request::Ehlo {
+                    host: "\u{4f8b}\u{5b50}.\u{5e7f}\u{544a}".into(),
+                },
+            ),
+        ] {
+            assert_eq!(
+                Request::parse_raw(&mut request.iter()),
+                Ok(raw),
+                "{request:?}"
+            );
+            assert_eq!(
+                Request::parse(&mut request.iter()),
+                Ok(lossy),
+                "{request:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_request_lenient() {
+        for (request, expected) in [
+            (
+                "MAIL FROM:<john@example.org> SIZE=abc BODY=8BITMIME X-FOO=bar",
+                Ok((
+                    Request::Mail {
+                        from: MailFrom {
+                            address: "john@example.org".into(),
+                            body: Some(Body::EightBitMime),
+                            ..Default::default()
+                        },
+                    },
+                    vec![
+                        Error::InvalidParameter {
+                            param: "SIZE",
+                            context: context("MAIL", 34, 3),
+                        },
+                        Error::UnsupportedParameter {
+                            param: "X-FOO=BAR".into(),
+                            context: context("MAIL", 52, 9),
+                        },
+                    ],
+                )),
+            ),
+            (
+                "RCPT TO:<jane@example.org> NOTIFY=CHIMICHANGA ORCPT=rfc822;jane@example.org >>",
+                Ok((
+                    Request::Rcpt {
+                        to: RcptTo {
+                            address: "jane@example.org".into(),
+                            orcpt: Some("jane@example.org".into()),
+                            ..Default::default()
+                        },
+                    },
+                    vec![
+                        Error::InvalidParameter {
+                            param: "NOTIFY",
+                            context: context("RCPT", 34, 11),
+                        },
+                        Error::SyntaxError {
+                            syntax: "RCPT TO:<forward-path> [parameters]",
+                            context: context("RCPT", 76, 2),
+                        },
+                    ],
+                )),
+            ),
+            (
+                "MAIL FROM:<john@example.org> SIZE=1000 SIZE=abc BODY=7BIT",
+                Ok((
+                    Request::Mail {
+                        from: MailFrom {
+                            address: "john@example.org".into(),
+                            body: Some(Body::SevenBit),
+                            size: 1000,
+                            ..Default::default()
+                        },
+                    },
+                    vec![Error::DuplicateParameter {
+                        param: "SIZE",
+                        context: context("MAIL", 39, 8),
+                    }],
+                )),
+            ),
+            (
+                "MAIL FROM:<john@example.org> SIZE=1000",
+                Ok((
+                    Request::Mail {
+                        from: MailFrom {
+                            address: "john@example.org".into(),
+                            size: 1000,
+                            ..Default::default()
+                        },
+                    },
+                    vec![],
+                )),
+            ),
+            (
+                "MAIL FROM:<@invalid> SIZE=abc",
+                Err(Error::InvalidSenderAddress {
+                    context: context("MAIL", 11, 8),
+                }),
+            ),
+            (
+                "MAIL FROM:<postmaster> SIZE=abc",
+                Ok((
+                    Request::Mail {
+                        from: "postmaster".into(),
+                    },
+                    vec![Error::InvalidParameter {
+                        param: "SIZE",
+                        context: context("MAIL", 28, 3),
+                    }],
+                )),
+            ),
+            (
+                "RCPT TO:<> NOTIFY=NEVER",
+                Err(Error::InvalidRecipientAddress {
+                    context: context("RCPT", 9, 0),
+                }),
+            ),
+        ] {
+            for extra in ["\n", "\r\n", " \n", " \r\n"] {
+                let request = format!("{request}{extra}QUIT\r\n");
+                let mut bytes = request.as_bytes().iter();
+                assert_eq!(
+                    expected,
+                    Request::parse_lenient(&mut bytes),
+                    "failed for {request:?}"
+                );
+                assert_eq!(Ok(Request::Quit), Request::parse(&mut bytes));
+            }
+        }
+    }
+
+    fn context(verb: &'static str, offset: usize, len: usize) -> ErrorContext {
+        ErrorContext { verb, offset, len }
+    }
+
+    impl<'a> From<&'a str> for ReversePath<Cow<'a, str>> {
+        fn from(value: &'a str) -> Self {
+            if value.is_empty() {
+                ReversePath::Null
+            } else {
+                ReversePath::Mailbox(value.into())
+            }
+        }
+    }
+
+    impl<'a> From<&'a str> for ForwardPath<Cow<'a, str>> {
+        fn from(value: &'a str) -> Self {
+            if value.eq_ignore_ascii_case("postmaster") {
+                ForwardPath::Postmaster
+            } else {
+                ForwardPath::Mailbox(value.into())
+            }
+        }
+    }
+
     impl<'a> From<&'a str> for MailFrom<Cow<'a, str>> {
         fn from(value: &'a str) -> Self {
             Self {
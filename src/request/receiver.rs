@@ -4,12 +4,21 @@
  * SPDX-License-Identifier: Apache-2.0 OR MIT
  */
 
-use std::{borrow::Cow, slice::Iter};
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+    slice::Iter,
+};
 
-use crate::{Error, Request};
+use memchr::{memchr, memchr2, memmem};
+
+use crate::{BareLfPolicy, BareLineEnding, Body, DataStatistics, Error, Request};
 
 pub const MAX_LINE_LENGTH: usize = 4096;
 
+/// Longest end of data prefix, `CR LF . CR`, held back by `DataReceiver`.
+const MAX_HELD_LEN: usize = 3;
+
 #[derive(Default)]
 pub struct RequestReceiver {
     buf: Vec<u8>,
@@ -18,21 +27,45 @@ pub struct RequestReceiver {
 
 pub struct DataReceiver {
     crlf_dot: bool,
+    data_start: bool,
     last_ch: u8,
     prev_last_ch: u8,
+    dot_len: usize,
+    size: usize,
+    max_size: usize,
+    is_exceeded: bool,
+    bare_lf: BareLfPolicy,
+    bare_line_ending: Option<BareLineEnding>,
+    discard: bool,
+    collect_stats: bool,
+    stats: DataStatistics,
+    line_len: usize,
+    header: HeaderScanner,
+    prepend: Vec<u8>,
 }
 
 pub struct BdatReceiver {
     pub is_last: bool,
     bytes_left: usize,
+    size: usize,
+    max_size: usize,
+    is_exceeded: bool,
+    header: HeaderScanner,
+    prepend: Vec<u8>,
+}
+
+/// Finds the first empty line of a message received in pieces.
+#[derive(Debug, Clone, Copy)]
+struct HeaderScanner {
+    offset: usize,
+    tail: [u8; 2],
+    header_end: Option<usize>,
 }
 
 pub struct DummyDataReceiver {
     is_bdat: bool,
     bdat_bytes_left: usize,
-    crlf_dot: bool,
-    last_ch: u8,
-    prev_last_ch: u8,
+    data: DataReceiver,
 }
 
 #[derive(Default)]
@@ -58,6 +91,32 @@ impl RequestReceiver {
         &'this mut self,
         bytes: &mut Iter<'bytes, u8>,
     ) -> Result<Request<Cow<'out, str>>, Error>
+    where
+        'this: 'out,
+        'bytes: 'out,
+    {
+        #[allow(clippy::redundant_closure)] // `Request::parse` is not generic over the lifetime
+        self.ingest_with(bytes, |bytes| Request::parse(bytes))
+    }
+
+    /// Same as [`RequestReceiver::ingest`], without decoding values as UTF-8.
+    pub fn ingest_raw<'this, 'bytes, 'out>(
+        &'this mut self,
+        bytes: &mut Iter<'bytes, u8>,
+    ) -> Result<Request<Cow<'out, [u8]>>, Error>
+    where
+        'this: 'out,
+        'bytes: 'out,
+    {
+        #[allow(clippy::redundant_closure)]
+        self.ingest_with(bytes, |bytes| Request::parse_raw(bytes))
+    }
+
+    fn ingest_with<'this, 'bytes, 'out, B: ?Sized + ToOwned + 'static>(
+        &'this mut self,
+        bytes: &mut Iter<'bytes, u8>,
+        parse: impl for<'z> Fn(&mut Iter<'z, u8>) -> Result<Request<Cow<'z, B>>, Error>,
+    ) -> Result<Request<Cow<'out, B>>, Error>
     where
         'this: 'out,
         'bytes: 'out,
@@ -66,7 +125,7 @@ impl RequestReceiver {
 
         if self.buf.is_empty() {
             let buf = bytes.as_slice();
-            match Request::parse(bytes) {
+            match parse(bytes) {
                 Err(Error::NeedsMoreData { bytes_left }) => {
                     if bytes_left > 0 {
                         if bytes_left < MAX_LINE_LENGTH {
@@ -83,7 +142,7 @@ impl RequestReceiver {
                 self.buf.push(ch);
                 if ch == b'\n' {
                     self.buf_used = true;
-                    return Request::parse(&mut self.buf.iter());
+                    return parse(&mut self.buf.iter());
                 } else if self.buf.len() == MAX_LINE_LENGTH {
                     self.buf.clear();
                     return Err(Error::ResponseTooLong);
@@ -100,34 +159,381 @@ impl DataReceiver {
     pub fn new() -> Self {
         Self {
             crlf_dot: false,
+            data_start: true,
             last_ch: 0,
             prev_last_ch: 0,
+            dot_len: 0,
+            size: 0,
+            max_size: usize::MAX,
+            is_exceeded: false,
+            bare_lf: BareLfPolicy::Accept,
+            bare_line_ending: None,
+            discard: false,
+            collect_stats: false,
+            stats: DataStatistics::default(),
+            line_len: 0,
+            header: HeaderScanner::default(),
+            prepend: Vec::new(),
+        }
+    }
+
+    /// Adds a header, such as `Received:` or `Return-Path:`, to write before
+    /// the message. It must end with CRLF and is not counted in the message
+    /// size or statistics.
+    pub fn with_header(mut self, header: impl AsRef<[u8]>) -> Self {
+        self.prepend.extend_from_slice(header.as_ref());
+        self
+    }
+
+    /// Returns the offset in the output of the first empty line, which ends the
+    /// header section, including any headers added with
+    /// [`DataReceiver::with_header`]. Once it is known, the headers are
+    /// complete even if the rest of the message has not arrived yet.
+    pub fn header_end(&self) -> Option<usize> {
+        self.header.header_end
+    }
+
+    /// Limits the size of the un-stuffed message. Once it is exceeded, `buf`
+    /// keeps the first `max_size` bytes, the rest of the message is consumed
+    /// without being buffered and [`DataReceiver::is_exceeded`] returns `true`.
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Collects the [`DataStatistics`] of the message. This looks at every line
+    /// ending and byte, so it is slower and disabled by default.
+    pub fn with_statistics(mut self, statistics: bool) -> Self {
+        self.collect_stats = statistics;
+        self
+    }
+
+    /// Sets how bare CR and LF characters are handled.
+    pub fn with_bare_lf_policy(mut self, policy: BareLfPolicy) -> Self {
+        self.bare_lf = policy;
+        self
+    }
+
+    /// Returns `true` if the message is larger than the maximum size, in which
+    /// case `buf` does not hold the whole message and the server should reply
+    /// with [`Error::MessageTooLarge`] after the end of data.
+    pub fn is_exceeded(&self) -> bool {
+        self.is_exceeded
+    }
+
+    /// Returns the first bare CR or LF found, or [`BareLineEnding::LfDotLf`]
+    /// if it ended the message. Always `None` with [`BareLfPolicy::Accept`].
+    pub fn bare_line_ending(&self) -> Option<BareLineEnding> {
+        self.bare_line_ending
+    }
+
+    /// Returns the error to reply with after the end of data, if the message
+    /// was too large or rejected by [`BareLfPolicy::Reject`].
+    pub fn error(&self) -> Option<Error> {
+        if self.is_exceeded {
+            Some(Error::MessageTooLarge)
+        } else if self.bare_lf == BareLfPolicy::Reject {
+            self.bare_line_ending
+                .map(|ending| Error::InvalidLineEnding { ending })
+        } else {
+            None
         }
     }
 
     pub fn ingest(&mut self, bytes: &mut Iter<'_, u8>, buf: &mut Vec<u8>) -> bool {
-        for &ch in bytes {
+        if !self.prepend.is_empty() {
+            let header = std::mem::take(&mut self.prepend);
+            if !self.discard {
+                buf.extend_from_slice(&header);
+            }
+            self.header.scan(&header);
+        }
+
+        let is_done = self.receive(bytes, buf);
+        if !self.is_exceeded
+            && self.size - if is_done { 0 } else { self.held_len() } > self.max_size
+        {
+            self.exceed(buf);
+        }
+        is_done
+    }
+
+    /// Stops buffering, keeping the first `max_size` bytes of the message.
+    fn exceed(&mut self, buf: &mut Vec<u8>) {
+        self.is_exceeded = true;
+        if !self.discard {
+            self.discard = true;
+            // Bytes past the limit have not been written yet by `ingest_write`
+            buf.truncate(buf.len() - self.size.saturating_sub(self.max_size));
+        }
+    }
+
+    fn receive(&mut self, bytes: &mut Iter<'_, u8>, buf: &mut Vec<u8>) -> bool {
+        let is_accept = self.bare_lf == BareLfPolicy::Accept;
+        let is_legacy = self.bare_lf == BareLfPolicy::LegacyTerminator;
+
+        loop {
+            if !self.crlf_dot && !self.data_start && !matches!(self.last_ch, b'\r' | b'\n') {
+                let data = bytes.as_slice();
+                let span_len = if self.collect_stats {
+                    self.count_lines(data)
+                } else if is_accept {
+                    // Only a dot after CRLF needs attention, copy everything before it at once
+                    memmem::find(data, b"\r\n.").map_or(data.len(), |pos| pos + 2)
+                } else {
+                    // Other policies have to look at every CR and LF
+                    memchr2(b'\r', b'\n', data).unwrap_or(data.len())
+                };
+                let (span, rest) = data.split_at(span_len);
+                self.extend(buf, span);
+                match span {
+                    [.., prev, last] => {
+                        self.prev_last_ch = *prev;
+                        self.last_ch = *last;
+                    }
+                    [last] => {
+                        self.prev_last_ch = self.last_ch;
+                        self.last_ch = *last;
+                    }
+                    [] => (),
+                }
+                *bytes = rest.iter();
+            }
+
+            let Some(&ch) = bytes.next() else {
+                return false;
+            };
+            if self.last_ch == b'\r' && ch != b'\n' {
+                if self.collect_stats {
+                    self.stats.bare_cr += 1;
+                    self.line_len += 1;
+                }
+                if !is_accept {
+                    // The CR is already buffered and no longer ends a dot line
+                    self.found(BareLineEnding::Cr);
+                    if self.bare_lf == BareLfPolicy::Normalize {
+                        self.extend(buf, b"\n");
+                    }
+                    self.crlf_dot = false;
+                    self.data_start = false;
+                }
+            } else if ch == b'\n' && self.last_ch != b'\r' {
+                if self.collect_stats {
+                    self.stats.bare_lf += 1;
+                }
+                if !is_accept {
+                    self.found(BareLineEnding::Lf);
+                    if self.bare_lf == BareLfPolicy::Normalize {
+                        self.extend(buf, b"\r");
+                    }
+                }
+            }
             match ch {
-                b'.' if self.last_ch == b'\n' && self.prev_last_ch == b'\r' => {
+                // The CRLF ending the DATA command precedes the first line
+                b'.' if self.last_ch == b'\n' && (self.prev_last_ch == b'\r' || is_legacy)
+                    || self.data_start && !self.crlf_dot =>
+                {
                     self.crlf_dot = true;
+                    self.dot_len = if self.data_start {
+                        0
+                    } else if self.prev_last_ch == b'\r' {
+                        2
+                    } else {
+                        1
+                    };
                 }
-                b'\n' if self.crlf_dot && self.last_ch == b'\r' => {
-                    buf.truncate(buf.len() - 3);
+                b'\n' if self.crlf_dot && (self.last_ch == b'\r' || is_legacy) => {
+                    let len = self.dot_len + usize::from(self.last_ch == b'\r');
+                    if !self.discard {
+                        buf.truncate(buf.len() - len);
+                    }
+                    self.size -= len;
+                    if self.dot_len == 1 || self.last_ch != b'\r' {
+                        self.bare_line_ending = Some(BareLineEnding::LfDotLf);
+                    }
                     return true;
                 }
                 b'\r' => {
-                    buf.push(ch);
+                    self.extend(buf, &[ch]);
+                    self.data_start &= self.crlf_dot;
+                }
+                b'\n' => {
+                    self.extend(buf, &[ch]);
+                    self.crlf_dot = false;
+                    self.data_start = false;
+                    if self.collect_stats {
+                        self.end_line();
+                    }
                 }
                 _ => {
-                    buf.push(ch);
+                    self.extend(buf, &[ch]);
                     self.crlf_dot = false;
+                    self.data_start = false;
+                    if self.collect_stats {
+                        self.line_len += 1;
+                        self.stats.has_8bit |= !ch.is_ascii();
+                        self.stats.has_nul |= ch == 0;
+                    }
                 }
             }
             self.prev_last_ch = self.last_ch;
             self.last_ch = ch;
         }
+    }
 
-        false
+    #[inline(always)]
+    fn extend(&mut self, buf: &mut Vec<u8>, bytes: &[u8]) {
+        if !self.discard {
+            // Up to MAX_HELD_LEN bytes past the limit may still be removed by
+            // the terminator, anything more exceeds it.
+            if self.size + bytes.len() > self.max_size.saturating_add(MAX_HELD_LEN) {
+                buf.extend_from_slice(&bytes[..self.max_size.saturating_sub(self.size)]);
+                self.exceed(buf);
+            } else {
+                buf.extend_from_slice(bytes);
+            }
+        }
+        self.size += bytes.len();
+        self.header.scan(bytes);
+    }
+
+    /// Counts the whole lines at the start of `data`, up to a line ending that
+    /// needs attention, and returns their length.
+    fn count_lines(&mut self, data: &[u8]) -> usize {
+        let mut pos = 0;
+        let len = loop {
+            let line_end = memchr2(b'\r', b'\n', &data[pos..]).map_or(data.len(), |end| pos + end);
+            self.line_len += line_end - pos;
+            if data[line_end..].starts_with(b"\r\n")
+                && data
+                    .get(line_end + 2)
+                    .is_some_and(|ch| !matches!(ch, b'.' | b'\r' | b'\n'))
+            {
+                self.end_line();
+                pos = line_end + 2;
+            } else {
+                break line_end;
+            }
+        };
+        self.stats.has_8bit |= !data[..len].is_ascii();
+        self.stats.has_nul |= memchr(0, &data[..len]).is_some();
+        len
+    }
+
+    fn end_line(&mut self) {
+        self.stats.lines += 1;
+        self.stats.max_line_len = self.stats.max_line_len.max(self.line_len);
+        self.line_len = 0;
+    }
+
+    /// Returns the statistics of the message received so far, if enabled with
+    /// [`DataReceiver::with_statistics`].
+    pub fn statistics(&self) -> Option<&DataStatistics> {
+        self.collect_stats.then_some(&self.stats)
+    }
+
+    fn found(&mut self, ending: BareLineEnding) {
+        self.bare_line_ending.get_or_insert(ending);
+        if self.bare_lf == BareLfPolicy::Reject {
+            self.discard = true;
+        }
+    }
+
+    /// Same as [`DataReceiver::ingest`], but writes the message to `writer` as
+    /// it arrives. Between calls, `buf` keeps the last few bytes that could
+    /// still be part of the terminator, so it must be reused for the whole
+    /// message.
+    pub fn ingest_write(
+        &mut self,
+        bytes: &mut Iter<'_, u8>,
+        buf: &mut Vec<u8>,
+        mut writer: impl Write,
+    ) -> io::Result<bool> {
+        let is_done = self.ingest(bytes, buf);
+        // Nothing is held back once the rest of the message is discarded
+        let len = buf.len()
+            - if is_done || self.discard {
+                0
+            } else {
+                self.held_len()
+            };
+        writer.write_all(&buf[..len])?;
+        buf.drain(..len);
+        Ok(is_done)
+    }
+
+    /// Number of trailing bytes that `ingest` may still remove, at most
+    /// `MAX_HELD_LEN`.
+    fn held_len(&self) -> usize {
+        if self.crlf_dot {
+            self.dot_len + usize::from(self.last_ch == b'\r')
+        } else if self.last_ch == b'\n' && self.prev_last_ch == b'\r' {
+            2
+        } else if self.last_ch == b'\n' {
+            usize::from(self.bare_lf == BareLfPolicy::LegacyTerminator)
+        } else {
+            usize::from(self.last_ch == b'\r')
+        }
+    }
+}
+
+impl Default for HeaderScanner {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            // The message starts on a new line
+            tail: [0, b'\n'],
+            header_end: None,
+        }
+    }
+}
+
+impl HeaderScanner {
+    fn scan(&mut self, bytes: &[u8]) {
+        if self.header_end.is_some() || bytes.is_empty() {
+            return;
+        }
+
+        // Empty lines that start in the last two bytes of the previous call
+        let head = &bytes[..bytes.len().min(2)];
+        let mut window = [0; 4];
+        window[..2].copy_from_slice(&self.tail);
+        window[2..2 + head.len()].copy_from_slice(head);
+        self.header_end = find_empty_line(&window[..2 + head.len()])
+            .map(|pos| self.offset + pos - 2)
+            .or_else(|| find_empty_line(bytes).map(|pos| self.offset + pos));
+
+        self.offset += bytes.len();
+        self.tail = match bytes {
+            [.., prev, last] => [*prev, *last],
+            [last] => [self.tail[1], *last],
+            [] => self.tail,
+        };
+    }
+}
+
+/// Returns the position after the first LF that is followed by an empty line.
+fn find_empty_line(bytes: &[u8]) -> Option<usize> {
+    let crlf = memmem::find(bytes, b"\n\r\n");
+    // A bare LF line can only come first if it is before the CRLF one
+    memmem::find(&bytes[..crlf.map_or(bytes.len(), |pos| pos + 1)], b"\n\n")
+        .or(crlf)
+        .map(|pos| pos + 1)
+}
+
+impl DataStatistics {
+    /// Returns `true` if the message can be sent with the given `BODY` type:
+    /// lines of at most 998 octets without NUL or bare CR and LF for `8BITMIME`
+    /// (RFC 6152), also without 8-bit octets for `7BIT`. Anything goes with
+    /// `BINARYMIME`, which cannot be used with `DATA` (RFC 3030).
+    pub fn conforms_to(&self, body: Body) -> bool {
+        match body {
+            Body::SevenBit => !self.has_8bit && self.conforms_to(Body::EightBitMime),
+            Body::EightBitMime => {
+                self.max_line_len <= 998 && !self.has_nul && self.bare_cr == 0 && self.bare_lf == 0
+            }
+            Body::BinaryMime => true,
+        }
     }
 }
 
@@ -136,19 +542,86 @@ impl BdatReceiver {
         Self {
             bytes_left: chunk_size,
             is_last,
+            size: 0,
+            max_size: usize::MAX,
+            is_exceeded: false,
+            header: HeaderScanner::default(),
+            prepend: Vec::new(),
         }
     }
 
-    pub fn ingest(&mut self, bytes: &mut Iter<'_, u8>, buf: &mut Vec<u8>) -> bool {
-        while self.bytes_left > 0 {
-            if let Some(&ch) = bytes.next() {
-                buf.push(ch);
-                self.bytes_left -= 1;
-            } else {
-                return false;
-            }
+    /// Returns the receiver for the next `BDAT` command of the same message,
+    /// which keeps looking for the end of the header section and enforcing the
+    /// maximum size of the whole message.
+    pub fn next_chunk(self, chunk_size: usize, is_last: bool) -> Self {
+        Self {
+            bytes_left: chunk_size,
+            is_last,
+            is_exceeded: self.is_exceeded || self.size + chunk_size > self.max_size,
+            ..self
         }
-        true
+    }
+
+    /// Adds a header to write before the chunk, see [`DataReceiver::with_header`].
+    pub fn with_header(mut self, header: impl AsRef<[u8]>) -> Self {
+        self.prepend.extend_from_slice(header.as_ref());
+        self
+    }
+
+    /// Returns the offset in the output of the first empty line, counting from
+    /// the first chunk of the message, see [`DataReceiver::header_end`].
+    pub fn header_end(&self) -> Option<usize> {
+        self.header.header_end
+    }
+
+    /// Limits the size of the message, across all the chunks received with
+    /// [`BdatReceiver::next_chunk`]. Bytes past the limit are consumed without
+    /// being buffered and [`BdatReceiver::is_exceeded`] returns `true`.
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self.is_exceeded = self.size + self.bytes_left > max_size;
+        self
+    }
+
+    /// Returns `true` if the chunks so far are larger than the maximum size,
+    /// in which case the server should reply with [`Error::MessageTooLarge`]
+    /// once the chunk has been consumed.
+    pub fn is_exceeded(&self) -> bool {
+        self.is_exceeded
+    }
+
+    pub fn ingest(&mut self, bytes: &mut Iter<'_, u8>, buf: &mut Vec<u8>) -> bool {
+        let (header, chunk, buffered) = self.take(bytes);
+        buf.extend_from_slice(&header);
+        buf.extend_from_slice(&chunk[..buffered]);
+        self.bytes_left == 0
+    }
+
+    /// Same as [`BdatReceiver::ingest`], but writes the chunk to `writer`.
+    pub fn ingest_write(
+        &mut self,
+        bytes: &mut Iter<'_, u8>,
+        mut writer: impl Write,
+    ) -> io::Result<bool> {
+        let (header, chunk, buffered) = self.take(bytes);
+        writer.write_all(&header)?;
+        writer.write_all(&chunk[..buffered])?;
+        Ok(self.bytes_left == 0)
+    }
+
+    /// Consumes the available part of the chunk. Returns the pending header,
+    /// the bytes taken and how many of them fit within the maximum size.
+    fn take<'x>(&mut self, bytes: &mut Iter<'x, u8>) -> (Vec<u8>, &'x [u8], usize) {
+        let data = bytes.as_slice();
+        let (chunk, rest) = data.split_at(self.bytes_left.min(data.len()));
+        let header = std::mem::take(&mut self.prepend);
+        let buffered = chunk.len().min(self.max_size.saturating_sub(self.size));
+        self.header.scan(&header);
+        self.header.scan(chunk);
+        self.bytes_left -= chunk.len();
+        self.size += chunk.len();
+        *bytes = rest.iter();
+        (header, chunk, buffered)
     }
 }
 
@@ -157,9 +630,7 @@ impl DummyDataReceiver {
         Self {
             bdat_bytes_left: chunk_size,
             is_bdat: true,
-            crlf_dot: false,
-            last_ch: 0,
-            prev_last_ch: 0,
+            data: DataReceiver::new(),
         }
     }
 
@@ -167,32 +638,17 @@ impl DummyDataReceiver {
         Self {
             is_bdat: false,
             bdat_bytes_left: 0,
-            crlf_dot: data.crlf_dot,
-            last_ch: data.last_ch,
-            prev_last_ch: data.prev_last_ch,
+            data: DataReceiver {
+                discard: true,
+                prepend: Vec::new(),
+                ..*data
+            },
         }
     }
 
     pub fn ingest(&mut self, bytes: &mut Iter<'_, u8>) -> bool {
         if !self.is_bdat {
-            for &ch in bytes {
-                match ch {
-                    b'.' if self.last_ch == b'\n' && self.prev_last_ch == b'\r' => {
-                        self.crlf_dot = true;
-                    }
-                    b'\n' if self.crlf_dot && self.last_ch == b'\r' => {
-                        return true;
-                    }
-                    b'\r' => {}
-                    _ => {
-                        self.crlf_dot = false;
-                    }
-                }
-                self.prev_last_ch = self.last_ch;
-                self.last_ch = ch;
-            }
-
-            false
+            self.data.ingest(bytes, &mut Vec::new())
         } else {
             while self.bdat_bytes_left > 0 {
                 if bytes.next().is_some() {
@@ -244,8 +700,13 @@ impl DummyLineReceiver {
 
 #[cfg(test)]
 mod tests {
-    use super::DataReceiver;
-    use crate::{Error, MailFrom, RcptTo, Request, request::receiver::RequestReceiver};
+    use super::{BdatReceiver, DataReceiver, DummyDataReceiver};
+    use crate::{
+        BareLfPolicy, BareLineEnding, Body, DataStatistics, Error, ForwardPath, MailFrom, RcptTo,
+        Request, ReversePath,
+        request::{encoder::DataEncoder, receiver::RequestReceiver},
+        test_support::{PerByteDataReceiver, xorshift},
+    };
 
     #[test]
     fn data_receiver() {
@@ -258,6 +719,12 @@ mod tests {
                 vec!["\r\na\rb\nc\r\n.d\r\n..\r\n", "\r\n.\r\n"],
                 "\r\na\rb\nc\r\nd\r\n.\r\n",
             ),
+            (vec![".\r\n"], ""),
+            (vec!["..hi\r\n", ".\r\n"], ".hi"),
+            (vec![".", "\r", "\n"], ""),
+            (vec![".a\r\n", ".b\r\n.\r\n"], "a\r\nb"),
+            (vec![".\r", "\n.\r\n"], ""),
+            (vec!["\r\n.\r\n"], ""),
             // Test SMTP smuggling attempts
             (
                 vec![
@@ -332,6 +799,470 @@ mod tests {
         }
     }
 
+    #[test]
+    fn data_receiver_spans() {
+        let mut next = xorshift(0x9e3779b97f4a7c15);
+
+        for _ in 0..5000 {
+            let data = (0..next() % 48)
+                .map(|_| b"..\r\r\n\nab"[(next() % 8) as usize])
+                .collect::<Vec<_>>();
+            let chunk_size = 1 + (next() % 8) as usize;
+            let statistics = next().is_multiple_of(2);
+
+            let mut r = DataReceiver::new().with_statistics(statistics);
+            let mut buf = Vec::new();
+            let mut expected = PerByteDataReceiver::default();
+            let mut expected_buf = Vec::new();
+            let mut w = DataReceiver::new().with_statistics(statistics);
+            let mut held = Vec::new();
+            let mut written = Vec::new();
+            for chunk in data.chunks(chunk_size) {
+                let mut bytes = chunk.iter();
+                let mut expected_bytes = chunk.iter();
+                let done = r.ingest(&mut bytes, &mut buf);
+                assert_eq!(
+                    done,
+                    expected.ingest(&mut expected_bytes, &mut expected_buf),
+                    "{data:?}"
+                );
+                assert_eq!(buf, expected_buf, "{data:?}");
+                assert_eq!(bytes.as_slice(), expected_bytes.as_slice(), "{data:?}");
+
+                // Writing must only hold back what the terminator may remove
+                let mut bytes = chunk.iter();
+                assert_eq!(
+                    w.ingest_write(&mut bytes, &mut held, &mut written).unwrap(),
+                    done,
+                    "{data:?}"
+                );
+                assert!(held.len() <= 3, "{data:?}");
+                assert_eq!([&written[..], &held[..]].concat(), buf, "{data:?}");
+                assert_eq!(bytes.as_slice(), expected_bytes.as_slice(), "{data:?}");
+                if done {
+                    assert!(held.is_empty(), "{data:?}");
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn data_receiver_bare_lf() {
+        use BareLfPolicy::*;
+
+        for (data, policy, message, rest, ending) in [
+            ("a\n.\r\nX\r\n.\r\n", Accept, Some("a\n.\r\nX"), "", None),
+            (
+                "a\n.\r\nX\r\n.\r\n",
+                Reject,
+                None,
+                "",
+                Some(BareLineEnding::Lf),
+            ),
+            (
+                "a\n.\r\nX\r\n.\r\n",
+                Normalize,
+                Some("a\r\n.\r\nX"),
+                "",
+                Some(BareLineEnding::Lf),
+            ),
+            (
+                "a\n.\r\nX\r\n.\r\n",
+                LegacyTerminator,
+                Some("a"),
+                "X\r\n.\r\n",
+                Some(BareLineEnding::LfDotLf),
+            ),
+            ("a\r.\r\nX\r\n.\r\n", Accept, Some("a\r.\r\nX"), "", None),
+            (
+                "a\r.\r\nX\r\n.\r\n",
+                Reject,
+                None,
+                "",
+                Some(BareLineEnding::Cr),
+            ),
+            (
+                "a\r.\r\nX\r\n.\r\n",
+                Normalize,
+                Some("a\r\n.\r\nX"),
+                "",
+                Some(BareLineEnding::Cr),
+            ),
+            (
+                "a\r.\r\nX\r\n.\r\n",
+                LegacyTerminator,
+                Some("a\r.\r\nX"),
+                "",
+                Some(BareLineEnding::Cr),
+            ),
+            ("a\r\n.\nX\r\n.\r\n", Accept, Some("a\r\n\nX"), "", None),
+            (
+                "a\r\n.\nX\r\n.\r\n",
+                Reject,
+                None,
+                "",
+                Some(BareLineEnding::Lf),
+            ),
+            (
+                "a\r\n.\nX\r\n.\r\n",
+                Normalize,
+                Some("a\r\n\r\nX"),
+                "",
+                Some(BareLineEnding::Lf),
+            ),
+            (
+                "a\r\n.\nX\r\n.\r\n",
+                LegacyTerminator,
+                Some("a"),
+                "X\r\n.\r\n",
+                Some(BareLineEnding::LfDotLf),
+            ),
+            ("a\r\n.\r\r\n.\r\n", Accept, Some("a\r"), ".\r\n", None),
+            (
+                "a\r\n.\r\r\n.\r\n",
+                Normalize,
+                Some("a\r\n\r\n"),
+                "",
+                Some(BareLineEnding::Cr),
+            ),
+            (
+                ".\n",
+                LegacyTerminator,
+                Some(""),
+                "",
+                Some(BareLineEnding::LfDotLf),
+            ),
+            (
+                "a\n..\nb\n.\n",
+                LegacyTerminator,
+                Some("a\n.\nb"),
+                "",
+                Some(BareLineEnding::LfDotLf),
+            ),
+            ("a\r\n..\r\n.\r\n", Reject, Some("a\r\n."), "", None),
+            ("a\r\n..\r\n.\r\n", Normalize, Some("a\r\n."), "", None),
+            (
+                "a\r\n..\r\n.\r\n",
+                LegacyTerminator,
+                Some("a\r\n."),
+                "",
+                None,
+            ),
+        ] {
+            let mut r = DataReceiver::new().with_bare_lf_policy(policy);
+            let mut buf = Vec::new();
+            let mut bytes = data.as_bytes().iter();
+            assert!(r.ingest(&mut bytes, &mut buf), "{data:?} {policy:?}");
+            assert_eq!(bytes.as_slice(), rest.as_bytes(), "{data:?} {policy:?}");
+            assert_eq!(r.bare_line_ending(), ending, "{data:?} {policy:?}");
+            match message {
+                Some(message) => {
+                    assert_eq!(r.error(), None, "{data:?} {policy:?}");
+                    assert_eq!(
+                        String::from_utf8(buf).unwrap(),
+                        message,
+                        "{data:?} {policy:?}"
+                    );
+                }
+                None => assert_eq!(
+                    r.error(),
+                    ending.map(|ending| Error::InvalidLineEnding { ending }),
+                    "{data:?} {policy:?}"
+                ),
+            }
+
+            let mut r =
+                DummyDataReceiver::new_data(&DataReceiver::new().with_bare_lf_policy(policy));
+            let mut bytes = data.as_bytes().iter();
+            assert!(r.ingest(&mut bytes), "{data:?} {policy:?}");
+            assert_eq!(bytes.as_slice(), rest.as_bytes(), "{data:?} {policy:?}");
+        }
+
+        // A smuggled terminator kept by Normalize is stuffed again when relayed
+        let mut r = DataReceiver::new().with_bare_lf_policy(Normalize);
+        let mut buf = Vec::new();
+        assert!(r.ingest(&mut b"a\n.\r\nX\r\n.\r\n".iter(), &mut buf));
+        let mut encoded = Vec::new();
+        let mut encoder = DataEncoder::new();
+        encoder.encode(&buf, &mut encoded).unwrap();
+        encoder.finish(&mut encoded).unwrap();
+        assert_eq!(encoded, b"a\r\n..\r\nX\r\n.\r\n");
+
+        // Chunk boundaries must not change the outcome
+        let mut next = xorshift(0x853c49e6748fea9b);
+        for _ in 0..5000 {
+            let data = (0..next() % 48)
+                .map(|_| b"..\r\r\n\nab"[(next() % 8) as usize])
+                .collect::<Vec<_>>();
+            let policy = [Accept, Reject, Normalize, LegacyTerminator][(next() % 4) as usize];
+            let statistics = next().is_multiple_of(2);
+
+            let mut expected = DataReceiver::new()
+                .with_bare_lf_policy(policy)
+                .with_statistics(true);
+            let mut expected_buf = Vec::new();
+            let mut expected_bytes = data.iter();
+            let is_done = expected.ingest(&mut expected_bytes, &mut expected_buf);
+
+            let mut r = DataReceiver::new()
+                .with_bare_lf_policy(policy)
+                .with_statistics(statistics);
+            let mut held = Vec::new();
+            let mut written = Vec::new();
+            let mut rest = &data[..];
+            for chunk in data.chunks(1 + (next() % 8) as usize) {
+                let mut bytes = chunk.iter();
+                let done = r.ingest_write(&mut bytes, &mut held, &mut written).unwrap();
+                assert!(held.len() <= 3, "{data:?} {policy:?}");
+                rest = &rest[chunk.len() - bytes.len()..];
+                if done {
+                    break;
+                }
+            }
+            assert_eq!(rest, expected_bytes.as_slice(), "{data:?} {policy:?}");
+            assert_eq!(
+                r.bare_line_ending(),
+                expected.bare_line_ending(),
+                "{data:?} {policy:?}"
+            );
+            assert_eq!(r.error(), expected.error(), "{data:?} {policy:?}");
+            if statistics {
+                assert_eq!(r.statistics(), expected.statistics(), "{data:?} {policy:?}");
+            } else {
+                assert_eq!(r.statistics(), None, "{data:?} {policy:?}");
+            }
+            if r.error().is_none() {
+                written.extend_from_slice(&held);
+                assert_eq!(written, expected_buf, "{data:?} {policy:?}");
+                assert!(!is_done || held.is_empty(), "{data:?} {policy:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn data_receiver_statistics() {
+        let long_line = format!("{}\r\n.\r\n", "a".repeat(999));
+        for (data, lines, max_line_len, has_8bit, has_nul, bare_cr, bare_lf, body) in [
+            (".\r\n", 0, 0, false, false, 0, 0, Body::SevenBit),
+            ("\r\n.\r\n", 1, 0, false, false, 0, 0, Body::SevenBit),
+            (
+                "Subject: hi\r\n\r\nabc\r\n..\r\n.\r\n",
+                4,
+                11,
+                false,
+                false,
+                0,
+                0,
+                Body::SevenBit,
+            ),
+            (
+                "caf\u{e9}\r\n.\r\n",
+                1,
+                5,
+                true,
+                false,
+                0,
+                0,
+                Body::EightBitMime,
+            ),
+            ("a\0b\r\n.\r\n", 1, 3, false, true, 0, 0, Body::BinaryMime),
+            (
+                "a\rb\nc\r\n.\r\n",
+                2,
+                3,
+                false,
+                false,
+                1,
+                1,
+                Body::BinaryMime,
+            ),
+            (&long_line, 1, 999, false, false, 0, 0, Body::BinaryMime),
+        ] {
+            let mut r = DataReceiver::new().with_statistics(true);
+            assert!(r.ingest(&mut data.as_bytes().iter(), &mut Vec::new()));
+            let stats = r.statistics().unwrap();
+            assert_eq!(
+                *stats,
+                DataStatistics {
+                    lines,
+                    max_line_len,
+                    has_8bit,
+                    has_nul,
+                    bare_cr,
+                    bare_lf,
+                },
+                "{data:?}"
+            );
+            for other in [Body::SevenBit, Body::EightBitMime, Body::BinaryMime] {
+                assert_eq!(
+                    stats.conforms_to(other),
+                    other as u8 >= body as u8,
+                    "{data:?} {other:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn header_end() {
+        for (data, header, header_end) in [
+            ("Subject: hi\r\n\r\nbody\r\n.\r\n", "", Some(13)),
+            ("Subject: hi\r\n\r\n.\r\n", "", Some(13)),
+            ("Subject: hi\r\n.\r\n", "", None),
+            ("\r\nbody\r\n.\r\n", "", Some(0)),
+            ("\r\nbody\r\n.\r\n", "Return-Path: <>\r\n", Some(17)),
+            ("a: b\r\n\tc\r\n\r\n\r\n.\r\n", "X: y\r\n", Some(16)),
+            ("a: b\r\n..\r\n\r\n.\r\n", "", Some(9)),
+            ("a: b\r\n \r\nc\r\n.\r\n", "", None),
+            ("a: b\n\nc\r\n.\r\n", "", Some(5)),
+            ("a: b\n\r\nc\r\n.\r\n", "", Some(5)),
+            ("a: b\r\n\nc\r\n.\r\n", "", Some(6)),
+            ("\nbody\r\n.\r\n", "", Some(0)),
+            ("\nbody\r\n.\r\n", "X: y\r\n", Some(6)),
+            ("a: b\r\r\nc\r\n.\r\n", "", None),
+        ] {
+            for chunk_size in [1, 2, 3, data.len()] {
+                let mut r = DataReceiver::new().with_header(header);
+                let mut buf = Vec::new();
+                let mut seen = None;
+                for chunk in data.as_bytes().chunks(chunk_size) {
+                    let is_done = r.ingest(&mut chunk.iter(), &mut buf);
+                    if let Some(header_end) = r.header_end() {
+                        // The headers are available as soon as the empty line arrives
+                        seen.get_or_insert_with(|| buf[..header_end].to_vec());
+                    }
+                    if is_done {
+                        break;
+                    }
+                }
+                assert_eq!(r.header_end(), header_end, "{data:?} {chunk_size}");
+                assert!(buf.starts_with(header.as_bytes()), "{data:?}");
+                if let Some(header_end) = header_end {
+                    assert_eq!(seen.unwrap(), &buf[..header_end], "{data:?}");
+                    assert!(
+                        buf[header_end..].is_empty()
+                            || buf[header_end..].starts_with(b"\r\n")
+                            || buf[header_end..].starts_with(b"\n"),
+                        "{data:?}"
+                    );
+                }
+
+                // BDAT sees the same message without the dot-stuffing
+                let mut message = buf[header.len()..].to_vec();
+                if header_end.is_some_and(|end| end == buf.len()) {
+                    message.extend_from_slice(b"\r\n");
+                }
+                let mut r = BdatReceiver::new(0, false).with_header(header);
+                let mut written = Vec::new();
+                for chunk in message.chunks(chunk_size) {
+                    r = r.next_chunk(chunk.len(), false);
+                    assert!(r.ingest_write(&mut chunk.iter(), &mut written).unwrap());
+                }
+                assert_eq!(r.header_end(), header_end, "{data:?} {chunk_size}");
+                assert!(written.starts_with(header.as_bytes()), "{data:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn data_receiver_max_size() {
+        for (data, max_size, message, is_exceeded) in [
+            (vec!["hello\r\n.\r\n"], 5, "hello", false),
+            (vec!["hell", "o\r", "\n.", "\r\n"], 5, "hello", false),
+            (vec!["hello!\r\n.\r\n"], 5, "hello", true),
+            (vec!["hello!\r\n", ".\r\n"], 5, "hello", true),
+            (
+                vec!["hel", "lo!\r\n", "more\r\n", ".\r\n"],
+                5,
+                "hello",
+                true,
+            ),
+            (vec!["hello\r\n.", "\r\n"], 5, "hello", false),
+            (vec!["hello\r\n..\r\n.\r\n"], 5, "hello", true),
+            (vec!["..\r\n", ".\r\n"], 1, ".", false),
+            (vec![".\r\n"], 0, "", false),
+        ] {
+            let mut r = DataReceiver::new().with_max_size(max_size);
+            let mut buf = Vec::new();
+            let mut is_done = false;
+            for chunk in &data {
+                assert!(!is_done, "{data:?}");
+                let mut bytes = chunk.as_bytes().iter();
+                is_done = r.ingest(&mut bytes, &mut buf);
+                assert_eq!(bytes.len(), 0, "{data:?}");
+            }
+            assert!(is_done, "{data:?}");
+            assert_eq!(r.is_exceeded(), is_exceeded, "{data:?}");
+            assert_eq!(String::from_utf8(buf).unwrap(), message, "{data:?}");
+
+            let mut r = DataReceiver::new().with_max_size(max_size);
+            let mut held = Vec::new();
+            let mut written = Vec::new();
+            for chunk in &data {
+                r.ingest_write(&mut chunk.as_bytes().iter(), &mut held, &mut written)
+                    .unwrap();
+                assert!(written.len() + held.len() <= max_size + 3, "{data:?}");
+            }
+            assert_eq!(r.is_exceeded(), is_exceeded, "{data:?}");
+            assert_eq!(String::from_utf8(written).unwrap(), message, "{data:?}");
+        }
+
+        // The stream stays in sync after an oversized chunk
+        let mut r = BdatReceiver::new(6, true).with_max_size(5);
+        let mut buf = Vec::new();
+        let mut bytes = b"hello!QUIT\r\n".iter();
+        assert!(r.ingest(&mut bytes, &mut buf));
+        assert!(r.is_exceeded());
+        assert_eq!(buf, b"hello");
+        assert_eq!(bytes.as_slice(), b"QUIT\r\n");
+        assert!(!BdatReceiver::new(6, true).with_max_size(6).is_exceeded());
+
+        // The limit applies to the whole message
+        for (chunks, max_size, message, exceeded_at) in [
+            (vec!["hello", " ", "world"], 11, "hello world", None),
+            (vec!["hello", " ", "world"], 10, "hello worl", Some(2)),
+            (vec!["hello", " ", "world"], 5, "hello", Some(1)),
+            (vec!["hello", "", "world", "!"], 7, "hellowo", Some(2)),
+        ] {
+            for write in [false, true] {
+                let mut r = BdatReceiver::new(0, false).with_max_size(max_size);
+                let mut buf = Vec::new();
+                for (i, chunk) in chunks.iter().enumerate() {
+                    r = r.next_chunk(chunk.len(), i == chunks.len() - 1);
+                    assert_eq!(
+                        r.is_exceeded(),
+                        exceeded_at.is_some_and(|at| i >= at),
+                        "{chunks:?} {max_size} {i}"
+                    );
+                    for part in chunk.as_bytes().chunks(2) {
+                        let mut bytes = part.iter();
+                        if write {
+                            r.ingest_write(&mut bytes, &mut buf).unwrap();
+                        } else {
+                            r.ingest(&mut bytes, &mut buf);
+                        }
+                        assert_eq!(bytes.len(), 0);
+                    }
+                }
+                assert_eq!(String::from_utf8(buf).unwrap(), message, "{chunks:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn bdat_receiver_write() {
+        let mut receiver = BdatReceiver::new(6, true);
+        let mut written = Vec::new();
+        let mut bytes = b"ab\r\n".iter();
+        assert!(!receiver.ingest_write(&mut bytes, &mut written).unwrap());
+        assert_eq!(bytes.len(), 0);
+        let mut bytes = b".\r\nQUIT\r\n".iter();
+        assert!(receiver.ingest_write(&mut bytes, &mut written).unwrap());
+        assert_eq!(bytes.as_slice(), b"\nQUIT\r\n");
+        assert_eq!(written, b"ab\r\n.\r");
+    }
+
     #[test]
     fn request_receiver() {
         for (data, expected_requests) in [
@@ -361,11 +1292,16 @@ mod tests {
                     },
                     Request::Mail {
                         from: MailFrom {
-                            address: "hello@world.com".to_string(),
-                            flags: 0,
+                            address: ReversePath::Mailbox("hello@world.com".to_string()),
+                            route: vec![],
+                            body: None,
+                            ret: None,
+                            smtp_utf8: false,
+                            require_tls: false,
+                            conperm: false,
                             size: 0,
                             trans_id: None,
-                            by: 0,
+                            by: None,
                             env_id: None,
                             solicit: None,
                             mtrk: None,
@@ -377,10 +1313,12 @@ mod tests {
                     },
                     Request::Rcpt {
                         to: RcptTo {
-                            address: "test@domain.com".to_string(),
+                            address: ForwardPath::Mailbox("test@domain.com".to_string()),
+                            route: vec![],
                             orcpt: None,
-                            rrvs: 0,
-                            flags: 0,
+                            notify: None,
+                            rrvs: None,
+                            conneg: false,
                         },
                     },
                 ],
@@ -0,0 +1,334 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs LLC <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: Apache-2.0 OR MIT
+ */
+
+//! Validation of internationalized addresses and domains (RFC 6531).
+
+use std::ops::Deref;
+
+#[cfg(feature = "smtputf8")]
+use unicode_normalization::{char::is_combining_mark, is_nfc};
+
+use crate::*;
+
+impl<T> MailFrom<T>
+where
+    T: Deref<Target: AsRef<[u8]>>,
+{
+    /// Returns `true` if the reverse-path and its source route are plain ASCII.
+    pub fn is_ascii(&self) -> bool {
+        self.address.mailbox().is_none_or(is_ascii) && self.route.iter().all(is_ascii)
+    }
+}
+
+impl<T> RcptTo<T>
+where
+    T: Deref<Target: AsRef<[u8]>>,
+{
+    /// Returns `true` if the forward-path and its source route are plain ASCII.
+    pub fn is_ascii(&self) -> bool {
+        self.address.mailbox().is_none_or(is_ascii) && self.route.iter().all(is_ascii)
+    }
+}
+
+impl<T> Request<T>
+where
+    T: Deref<Target: AsRef<[u8]>>,
+{
+    /// Returns `true` if a `MAIL FROM` or `RCPT TO` path contains non-ASCII
+    /// bytes although `SMTPUTF8` was not declared. For `RCPT TO`, `smtp_utf8`
+    /// tells whether the `MAIL FROM` of the transaction declared it.
+    pub fn is_8bit_without_smtputf8(&self, smtp_utf8: bool) -> bool {
+        match self {
+            Request::Mail { from } => !from.smtp_utf8 && !from.is_ascii(),
+            Request::Rcpt { to } => !smtp_utf8 && !to.is_ascii(),
+            _ => false,
+        }
+    }
+
+    /// Validates the `MAIL FROM` and `RCPT TO` paths, including U-label domains,
+    /// and the `EHLO`, `HELO` or `LHLO` domain. Non-ASCII values must be NFC
+    /// normalized UTF-8 and are only allowed with `SMTPUTF8` or, for the greeting,
+    /// when `ehlo` accepts them. For `RCPT TO`, `smtp_utf8` tells whether the
+    /// `MAIL FROM` of the transaction declared `SMTPUTF8`. Malformed UTF-8 can only
+    /// be detected in requests returned by [`Request::parse_raw`].
+    ///
+    /// U-labels are only partially checked, see [`Utf8Violation::InvalidULabel`].
+    /// Requires the `smtputf8` feature.
+    #[cfg(feature = "smtputf8")]
+    pub fn validate_utf8(&self, smtp_utf8: bool, ehlo: Utf8EhloPolicy) -> Result<(), Error> {
+        let (verb, result) = match self {
+            Request::Mail { from } => (
+                "MAIL",
+                validate_path(from.address.mailbox(), &from.route, from.smtp_utf8),
+            ),
+            Request::Rcpt { to } => (
+                "RCPT",
+                validate_path(to.address.mailbox(), &to.route, smtp_utf8),
+            ),
+            Request::Ehlo { host } => ("EHLO", validate_host(host, ehlo)),
+            Request::Helo { host } => ("HELO", validate_host(host, ehlo)),
+            Request::Lhlo { host } => ("LHLO", validate_host(host, ehlo)),
+            _ => return Ok(()),
+        };
+        result.map_err(|violation| Error::InvalidUtf8 {
+            violation,
+            context: ErrorContext {
+                verb,
+                ..Default::default()
+            },
+        })
+    }
+}
+
+#[cfg(feature = "smtputf8")]
+fn validate_path<T: Deref<Target: AsRef<[u8]>>>(
+    mailbox: Option<&T>,
+    route: &[T],
+    smtp_utf8: bool,
+) -> Result<(), Utf8Violation> {
+    if let Some(mailbox) = mailbox {
+        let mailbox = validate_utf8(mailbox.deref().as_ref(), smtp_utf8)?;
+        if let Some((_, domain)) = mailbox.rsplit_once('@') {
+            validate_domain(domain)?;
+        }
+    }
+    for domain in route {
+        validate_domain(validate_utf8(domain.deref().as_ref(), smtp_utf8)?)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "smtputf8")]
+fn validate_host<T: Deref<Target: AsRef<[u8]>>>(
+    host: &T,
+    ehlo: Utf8EhloPolicy,
+) -> Result<(), Utf8Violation> {
+    validate_domain(validate_utf8(
+        host.deref().as_ref(),
+        ehlo == Utf8EhloPolicy::Accept,
+    )?)
+}
+
+#[cfg(feature = "smtputf8")]
+fn validate_utf8(value: &[u8], allow_8bit: bool) -> Result<&str, Utf8Violation> {
+    let is_ascii = value.is_ascii();
+    if !is_ascii && !allow_8bit {
+        return Err(Utf8Violation::EightBitWithoutSmtpUtf8);
+    }
+    let value = std::str::from_utf8(value).map_err(|_| Utf8Violation::MalformedUtf8)?;
+    if is_ascii || is_nfc(value) {
+        Ok(value)
+    } else {
+        Err(Utf8Violation::NotNfc)
+    }
+}
+
+#[cfg(feature = "smtputf8")]
+fn validate_domain(domain: &str) -> Result<(), Utf8Violation> {
+    // Address literals are plain ASCII
+    if domain.starts_with('[') || domain.split('.').all(is_u_label) {
+        Ok(())
+    } else {
+        Err(Utf8Violation::InvalidULabel)
+    }
+}
+
+/// Checks the U-label rules of RFC 5891, Section 5.4 that do not depend on
+/// the IDNA2008 tables: hyphen placement, leading combining marks and, as a
+/// stand-in for the PVALID code points, letters, digits and combining marks
+/// only. ASCII labels are accepted as they are.
+#[cfg(feature = "smtputf8")]
+fn is_u_label(label: &str) -> bool {
+    label.is_ascii()
+        || !label.starts_with('-')
+            && !label.ends_with('-')
+            && !label.chars().skip(2).take(2).eq("--".chars())
+            && !label.starts_with(is_combining_mark)
+            && label
+                .chars()
+                .all(|ch| ch == '-' || ch.is_alphanumeric() || is_combining_mark(ch))
+}
+
+fn is_ascii<T: Deref<Target: AsRef<[u8]>>>(value: &T) -> bool {
+    value.deref().as_ref().is_ascii()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Request;
+
+    #[test]
+    fn is_8bit_without_smtputf8() {
+        for (request, smtp_utf8, expected) in [
+            ("MAIL FROM:<jdoe@example.org>\r\n", false, false),
+            ("MAIL FROM:<>\r\n", false, false),
+            ("MAIL FROM:<δοκιμή@παράδειγμα.δοκιμή>\r\n", false, true),
+            (
+                "MAIL FROM:<δοκιμή@παράδειγμα.δοκιμή> SMTPUTF8\r\n",
+                false,
+                false,
+            ),
+            ("MAIL FROM:<@ñ.org:jdoe@example.org>\r\n", false, true),
+            ("MAIL FROM:<jdoé@example.org>\r\n", true, true),
+            ("RCPT TO:<jdoe@example.org>\r\n", false, false),
+            ("RCPT TO:<Postmaster>\r\n", false, false),
+            ("RCPT TO:<用户@例子.广告>\r\n", false, true),
+            ("RCPT TO:<用户@例子.广告>\r\n", true, false),
+            ("EHLO 例子.广告\r\n", false, false),
+        ] {
+            let raw = Request::parse_raw(&mut request.as_bytes().iter()).unwrap();
+            assert_eq!(
+                raw.is_8bit_without_smtputf8(smtp_utf8),
+                expected,
+                "{request:?}"
+            );
+            let request = Request::parse(&mut request.as_bytes().iter()).unwrap();
+            assert_eq!(
+                request.is_8bit_without_smtputf8(smtp_utf8),
+                expected,
+                "{request:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "smtputf8")]
+    fn validate_utf8() {
+        use crate::{Error, Utf8EhloPolicy, Utf8Violation};
+
+        for (request, smtp_utf8, ehlo, expected) in [
+            (
+                &b"MAIL FROM:<jdoe@example.org>"[..],
+                false,
+                Utf8EhloPolicy::Reject,
+                None,
+            ),
+            (
+                "MAIL FROM:<δοκιμή@παράδειγμα.δοκιμή>".as_bytes(),
+                false,
+                Utf8EhloPolicy::Reject,
+                Some(Utf8Violation::EightBitWithoutSmtpUtf8),
+            ),
+            (
+                "MAIL FROM:<δοκιμή@παράδειγμα.δοκιμή> SMTPUTF8".as_bytes(),
+                false,
+                Utf8EhloPolicy::Reject,
+                None,
+            ),
+            (
+                &b"MAIL FROM:<j\xe9@example.org> SMTPUTF8"[..],
+                false,
+                Utf8EhloPolicy::Reject,
+                Some(Utf8Violation::MalformedUtf8),
+            ),
+            (
+                "MAIL FROM:<cafe\u{301}@example.org> SMTPUTF8".as_bytes(),
+                false,
+                Utf8EhloPolicy::Reject,
+                Some(Utf8Violation::NotNfc),
+            ),
+            (
+                "MAIL FROM:<café@example.org> SMTPUTF8".as_bytes(),
+                false,
+                Utf8EhloPolicy::Reject,
+                None,
+            ),
+            (
+                "MAIL FROM:<@-ñ.org:jdoe@example.org> SMTPUTF8".as_bytes(),
+                false,
+                Utf8EhloPolicy::Reject,
+                Some(Utf8Violation::InvalidULabel),
+            ),
+            (
+                "RCPT TO:<用户@例子.广告>".as_bytes(),
+                false,
+                Utf8EhloPolicy::Reject,
+                Some(Utf8Violation::EightBitWithoutSmtpUtf8),
+            ),
+            (
+                "RCPT TO:<用户@例子.广告>".as_bytes(),
+                true,
+                Utf8EhloPolicy::Reject,
+                None,
+            ),
+            (
+                "RCPT TO:<user@\u{301}a.example>".as_bytes(),
+                true,
+                Utf8EhloPolicy::Reject,
+                Some(Utf8Violation::InvalidULabel),
+            ),
+            (
+                "RCPT TO:<user@ab--ñ.example>".as_bytes(),
+                true,
+                Utf8EhloPolicy::Reject,
+                Some(Utf8Violation::InvalidULabel),
+            ),
+            (
+                "RCPT TO:<user@üa--x.example>".as_bytes(),
+                true,
+                Utf8EhloPolicy::Reject,
+                Some(Utf8Violation::InvalidULabel),
+            ),
+            (
+                "RCPT TO:<user@ü--x.example>".as_bytes(),
+                true,
+                Utf8EhloPolicy::Reject,
+                None,
+            ),
+            (
+                "RCPT TO:<user@ñ_ñ.example>".as_bytes(),
+                true,
+                Utf8EhloPolicy::Reject,
+                Some(Utf8Violation::InvalidULabel),
+            ),
+            (
+                "RCPT TO:<user@xn--ida.example>".as_bytes(),
+                false,
+                Utf8EhloPolicy::Reject,
+                None,
+            ),
+            (
+                "EHLO 例子.广告".as_bytes(),
+                true,
+                Utf8EhloPolicy::Reject,
+                Some(Utf8Violation::EightBitWithoutSmtpUtf8),
+            ),
+            (
+                "EHLO 例子.广告".as_bytes(),
+                false,
+                Utf8EhloPolicy::Accept,
+                None,
+            ),
+            (
+                "LHLO -例子.广告".as_bytes(),
+                false,
+                Utf8EhloPolicy::Accept,
+                Some(Utf8Violation::InvalidULabel),
+            ),
+            (
+                &b"HELO [192.0.2.1]"[..],
+                false,
+                Utf8EhloPolicy::Reject,
+                None,
+            ),
+        ] {
+            let line = [request, b"\r\n"].concat();
+            let parsed = Request::parse_raw(&mut line.iter()).unwrap();
+            assert_eq!(
+                parsed
+                    .validate_utf8(smtp_utf8, ehlo)
+                    .map_err(|err| match err {
+                        Error::InvalidUtf8 { violation, .. } => violation,
+                        err => panic!("Unexpected error {err:?}"),
+                    })
+                    .err(),
+                expected,
+                "{:?}",
+                String::from_utf8_lossy(request)
+            );
+        }
+    }
+}
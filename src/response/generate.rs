@@ -15,7 +15,9 @@ impl<T: Display> EhloResponse<T> {
     pub fn new(hostname: T) -> Self {
         Self {
             hostname,
+            greeting: None,
             capabilities: 0,
+            capability_order: Vec::new(),
             auth_mechanisms: 0,
             deliver_by: 0,
             future_release_interval: 0,
@@ -26,12 +28,73 @@ impl<T: Display> EhloResponse<T> {
         }
     }
 
-    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
-        write!(writer, "250-{} you had me at EHLO\r\n", self.hostname)?;
+    /// Sets the text written after the hostname on the first line of the reply.
+    pub fn with_greeting(mut self, greeting: T) -> Self {
+        self.greeting = Some(greeting);
+        self
+    }
+
+    /// Adds one or more `EXT_*` capabilities, which will be written in the
+    /// order they were added. This is the only way to record the order, see
+    /// [`EhloResponse::capability_order`].
+    pub fn add_capability(&mut self, capability: u32) {
+        let mut capabilities = capability;
+        while capabilities != 0 {
+            let capability = 1 << capabilities.trailing_zeros();
+            capabilities ^= capability;
+
+            if !self.capability_order.contains(&capability) {
+                self.capability_order.push(capability);
+            }
+        }
+        self.capabilities |= capability;
+    }
+
+    pub fn write(&self, writer: impl Write) -> io::Result<()> {
+        self.write_with(writer, false)
+    }
+
+    /// Same as [`EhloResponse::write`] but strips CR, LF and other control characters
+    /// from the hostname, greeting and NO-SOLICITING keywords, so that none of them
+    /// can terminate the reply early or inject extra lines.
+    pub fn write_safe(&self, writer: impl Write) -> io::Result<()> {
+        self.write_with(writer, true)
+    }
+
+    fn write_with(&self, mut writer: impl Write, safe: bool) -> io::Result<()> {
+        let greeting = self
+            .greeting
+            .as_ref()
+            .map_or_else(|| "you had me at EHLO".to_string(), T::to_string);
+        let (hostname, greeting) = if safe {
+            (
+                sanitize(&self.hostname.to_string(), false),
+                sanitize(&greeting, false),
+            )
+        } else {
+            (self.hostname.to_string(), greeting)
+        };
+        write!(
+            writer,
+            "250{}{}{}{}\r\n",
+            if self.capabilities != 0 { "-" } else { " " },
+            hostname,
+            if greeting.is_empty() { "" } else { " " },
+            greeting
+        )?;
         let mut capabilities = self.capabilities;
+        let mut order = self.capability_order.iter();
 
         while capabilities != 0 {
-            let capability = 1 << (31 - capabilities.leading_zeros());
+            // Capabilities listed in `capability_order` go first, the rest
+            // are written from the highest bit to the lowest.
+            let capability = order
+                .by_ref()
+                .copied()
+                .find(|&capability| {
+                    capability.is_power_of_two() && (capabilities & capability) != 0
+                })
+                .unwrap_or_else(|| 1 << (31 - capabilities.leading_zeros()));
             capabilities ^= capability;
 
             writer.write_all(b"250")?;
@@ -85,7 +148,11 @@ impl<T: Display> EhloResponse<T> {
                 EXT_MTRK => write!(writer, "MTRK\r\n"),
                 EXT_NO_SOLICITING => {
                     if let Some(keywords) = &self.no_soliciting {
-                        write!(writer, "NO-SOLICITING {keywords}\r\n")
+                        if safe {
+                            write!(writer, "NO-SOLICITING {}\r\n", sanitize(keywords, false))
+                        } else {
+                            write!(writer, "NO-SOLICITING {keywords}\r\n")
+                        }
                     } else {
                         write!(writer, "NO-SOLICITING\r\n")
                     }
@@ -112,13 +179,202 @@ impl<T: Display> EhloResponse<T> {
     }
 }
 
+/// Maximum length of a reply line, including the trailing CRLF (RFC 5321 section 4.5.3.1.5).
+pub const MAX_REPLY_LINE_LENGTH: usize = 512;
+
 impl<T: Display> Response<T> {
-    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
-        write!(
-            writer,
-            "{} {}.{}.{} {}\r\n",
-            self.code, self.esc[0], self.esc[1], self.esc[2], self.message
+    /// Writes the reply. Messages containing line breaks are written as a
+    /// multi-line reply, repeating the enhanced status code on every line, and
+    /// lines that exceed [`MAX_REPLY_LINE_LENGTH`] are wrapped.
+    pub fn write(&self, writer: impl Write) -> io::Result<()> {
+        self.write_with(writer, false)
+    }
+
+    /// Same as [`Response::write`] but meant for messages that include untrusted
+    /// text such as addresses or upstream errors. Any CR, LF or CRLF starts a new
+    /// continuation line and all other control characters are removed, which
+    /// prevents a message from ending the reply early or forging another one.
+    pub fn write_safe(&self, writer: impl Write) -> io::Result<()> {
+        self.write_with(writer, true)
+    }
+
+    fn write_with(&self, mut writer: impl Write, safe: bool) -> io::Result<()> {
+        let message = if safe {
+            sanitize(&self.message.to_string(), true)
+        } else {
+            self.message.to_string()
+        };
+        let esc = self
+            .esc
+            .map(|esc| format!("{}.{}.{} ", esc[0], esc[1], esc[2]))
+            .unwrap_or_default();
+        let mut lines = ReplyLines::new(
+            message.trim_end_matches(['\r', '\n']),
+            MAX_REPLY_LINE_LENGTH - esc.len() - 6,
         )
+        .peekable();
+
+        while let Some(line) = lines.next() {
+            write!(
+                writer,
+                "{}{}{}{}\r\n",
+                self.code,
+                if lines.peek().is_some() { "-" } else { " " },
+                esc,
+                line
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes control characters other than tab from `text`. When `line_breaks` is set,
+/// CR, LF and CRLF are all converted to a single LF instead of being removed.
+pub(crate) fn sanitize(text: &str, line_breaks: bool) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\r' | '\n' if line_breaks => {
+                if ch == '\r' && chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                result.push('\n');
+            }
+            '\t' => result.push(ch),
+            _ if ch.is_control() => (),
+            _ => result.push(ch),
+        }
+    }
+
+    result
+}
+
+/// Splits a message into reply lines, wrapping the ones longer than `max_len` bytes
+/// at the last space or, if there is none, at the last character boundary.
+struct ReplyLines<'x> {
+    lines: std::str::Split<'x, char>,
+    line: Option<&'x str>,
+    max_len: usize,
+}
+
+impl<'x> ReplyLines<'x> {
+    fn new(message: &'x str, max_len: usize) -> Self {
+        Self {
+            lines: message.split('\n'),
+            line: None,
+            max_len,
+        }
+    }
+}
+
+impl<'x> Iterator for ReplyLines<'x> {
+    type Item = &'x str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = match self.line.take() {
+            Some(line) => line,
+            None => {
+                let line = self.lines.next()?;
+                line.strip_suffix('\r').unwrap_or(line)
+            }
+        };
+
+        if line.len() <= self.max_len {
+            return Some(line);
+        }
+
+        let mut split_pos = self.max_len;
+        while !line.is_char_boundary(split_pos) {
+            split_pos -= 1;
+        }
+        if let Some(space_pos) = line[..split_pos].rfind(' ').filter(|&pos| pos > 0) {
+            self.line = Some(&line[space_pos + 1..]);
+            Some(&line[..space_pos])
+        } else {
+            self.line = Some(&line[split_pos..]);
+            Some(&line[..split_pos])
+        }
+    }
+}
+
+impl Error {
+    /// Returns the reply a server should send when a command fails with this error.
+    /// Messages can include text sent by the client, so they should be written with
+    /// [`Response::write_safe`].
+    pub fn to_response(&self) -> Response<String> {
+        match self {
+            Error::NeedsMoreData { .. } => {
+                Response::new(500, 5, 5, 2, "Syntax error, incomplete command".to_string())
+            }
+            Error::UnknownCommand => {
+                Response::new(500, 5, 5, 1, "Command not recognized".to_string())
+            }
+            Error::InvalidSenderAddress { .. } => Response::new(
+                501,
+                5,
+                1,
+                7,
+                "Bad sender's mailbox address syntax".to_string(),
+            ),
+            Error::InvalidRecipientAddress { .. } => Response::new(
+                501,
+                5,
+                1,
+                3,
+                "Bad destination mailbox address syntax".to_string(),
+            ),
+            Error::SyntaxError { syntax, .. } => {
+                Response::new(501, 5, 5, 2, format!("Syntax error, expected: {syntax}"))
+            }
+            Error::InvalidParameter { param, .. } => {
+                Response::new(501, 5, 5, 4, format!("Invalid value for parameter {param}"))
+            }
+            Error::UnsupportedParameter { param, .. } => {
+                // Only echo the keyword, not the value sent by the client
+                let keyword = param
+                    .split_once('=')
+                    .map_or(param.as_str(), |(keyword, _)| keyword);
+                Response::new(555, 5, 5, 4, format!("Unsupported parameter {keyword}"))
+            }
+            Error::DuplicateParameter { param, .. } => {
+                Response::new(501, 5, 5, 4, format!("Duplicate parameter {param}"))
+            }
+            Error::InvalidUtf8 { violation, context }
+                if matches!(context.verb, "EHLO" | "HELO" | "LHLO") =>
+            {
+                Response::new(501, 5, 5, 4, format!("Invalid domain name: {violation}"))
+            }
+            Error::InvalidUtf8 {
+                violation: Utf8Violation::EightBitWithoutSmtpUtf8,
+                ..
+            } => Response::new(
+                553,
+                5,
+                6,
+                7,
+                "Non-ASCII addresses require SMTPUTF8".to_string(),
+            ),
+            Error::InvalidUtf8 { violation, .. } => {
+                Response::new(553, 5, 6, 7, format!("Invalid address: {violation}"))
+            }
+            Error::ResponseTooLong => Response::new(500, 5, 5, 2, "Line too long".to_string()),
+            Error::InvalidResponse { code } => {
+                Response::new(451, 4, 5, 0, format!("Unexpected response code {code}"))
+            }
+            Error::MessageTooLarge => Response::new(
+                552,
+                5,
+                3,
+                4,
+                "Message size exceeds fixed maximum message size".to_string(),
+            ),
+            Error::InvalidLineEnding { ending } => {
+                Response::new(550, 5, 5, 2, format!("Invalid line ending: {ending}"))
+            }
+        }
     }
 }
 
@@ -177,10 +433,25 @@ impl BitToString for u64 {
 }
 
 impl<T: Display> Response<T> {
+    /// Creates a new reply with an enhanced status code. Since `0.0.0` is not a
+    /// valid enhanced status code, passing all zeros creates a reply without one.
     pub fn new(code: u16, e0: u8, e1: u8, e2: u8, message: T) -> Self {
         Self {
             code,
-            esc: [e0, e1, e2],
+            esc: if [e0, e1, e2] != [0, 0, 0] {
+                Some([e0, e1, e2])
+            } else {
+                None
+            },
+            message,
+        }
+    }
+
+    /// Creates a new reply without an enhanced status code.
+    pub fn new_basic(code: u16, message: T) -> Self {
+        Self {
+            code,
+            esc: None,
             message,
         }
     }
@@ -190,6 +461,11 @@ impl<T: Display> Response<T> {
         self.code
     }
 
+    /// Returns the enhanced status code, if the reply included one.
+    pub fn esc(&self) -> Option<[u8; 3]> {
+        self.esc
+    }
+
     /// Returns the message included in the reply.
     pub fn message(&self) -> &T {
         &self.message
@@ -229,966 +505,365 @@ impl<T: Display> Response<T> {
         self.severity() == Severity::PositiveCompletion
     }
 
+    /// Returns the enhanced status code, if the reply included one.
+    pub fn enhanced_status_code(&self) -> Option<EnhancedStatusCode> {
+        self.esc.map(EnhancedStatusCode::from)
+    }
+
     pub fn explain_class_code(&self) -> Option<(&'static str, &'static str)> {
-        match self.esc[0] {
-            2 => (
-                "Success",
-                concat!(
-                    "Success specifies that the DSN is reporting a posi",
-                    "tive delivery action. Detail sub-codes may provide",
-                    " notification of transformations required for deli",
-                    "very."
-                ),
-            )
-                .into(),
-            4 => (
-                "Persistent Transient Failure",
-                concat!(
-                    "A persistent transient failure is one in which the",
-                    " message as sent is valid, but persistence of some",
-                    " temporary condition has caused abandonment or del",
-                    "ay of attempts to send the message. If this code a",
-                    "ccompanies a delivery failure report, sending in t",
-                    "he future may be successful."
-                ),
-            )
-                .into(),
-            5 => (
-                "Permanent Failure",
-                concat!(
-                    "A permanent failure is one which is not likely to ",
-                    "be resolved by resending the message in the curren",
-                    "t form. Some change to the message or the destinat",
-                    "ion must be made for successful delivery."
-                ),
-            )
-                .into(),
-            _ => None,
-        }
+        self.enhanced_status_code()?.explain_class()
     }
 
     pub fn explain_subject_code(&self) -> Option<(&'static str, &'static str)> {
-        match self.esc[1] {
-            0 => (
-                "Other or Undefined Status",
-                concat!("There is no additional subject information availab", "le."),
-            )
-                .into(),
-            1 => (
-                "Addressing Status",
-                concat!(
-                    "The address status reports on the originator or de",
-                    "stination address. It may include address syntax o",
-                    "r validity. These errors can generally be correcte",
-                    "d by the sender and retried."
-                ),
-            )
-                .into(),
-            2 => (
-                "Mailbox Status",
-                concat!(
-                    "Mailbox status indicates that something having to ",
-                    "do with the mailbox has caused this DSN. Mailbox i",
-                    "ssues are assumed to be under the general control ",
-                    "of the recipient."
-                ),
-            )
-                .into(),
-            3 => (
-                "Mail System Status",
-                concat!(
-                    "Mail system status indicates that something having",
-                    " to do with the destination system has caused this",
-                    " DSN. System issues are assumed to be under the ge",
-                    "neral control of the destination system administra",
-                    "tor."
-                ),
-            )
-                .into(),
-            4 => (
-                "Network and Routing Status",
-                concat!(
-                    "The networking or routing codes report status abou",
-                    "t the delivery system itself. These system compone",
-                    "nts include any necessary infrastructure such as d",
-                    "irectory and routing services. Network issues are ",
-                    "assumed to be under the control of the destination",
-                    " or intermediate system administrator."
-                ),
-            )
-                .into(),
-            5 => (
-                "Mail Delivery Protocol Status",
-                concat!(
-                    "The mail delivery protocol status codes report fai",
-                    "lures involving the message delivery protocol. The",
-                    "se failures include the full range of problems res",
-                    "ulting from implementation errors or an unreliable",
-                    " connection."
-                ),
-            )
-                .into(),
-            6 => (
-                "Message Content or Media Status",
-                concat!(
-                    "The message content or media status codes report f",
-                    "ailures involving the content of the message. Thes",
-                    "e codes report failures due to translation, transc",
-                    "oding, or otherwise unsupported message media. Mes",
-                    "sage content or media issues are under the control",
-                    " of both the sender and the receiver, both of whic",
-                    "h must support a common set of supported content-t",
-                    "ypes."
-                ),
-            )
-                .into(),
-            7 => (
-                "Security or Policy Status",
-                concat!(
-                    "The security or policy status codes report failure",
-                    "s involving policies such as per-recipient or per-",
-                    "host filtering and cryptographic operations. Secur",
-                    "ity and policy status issues are assumed to be und",
-                    "er the control of either or both the sender and re",
-                    "cipient. Both the sender and recipient must permit",
-                    " the exchange of messages and arrange the exchange",
-                    " of necessary keys and certificates for cryptograp",
-                    "hic operations."
-                ),
-            )
-                .into(),
+        self.enhanced_status_code()?.explain_subject()
+    }
 
-            _ => None,
+    pub fn explain_status_code(&self) -> Option<(&'static str, &'static str)> {
+        self.enhanced_status_code()?
+            .info()
+            .map(|info| (info.title, info.description))
+    }
+}
+
+/// Recipients accepted in an LMTP transaction. After the message data, the
+/// server sends one reply for each of them, in the order in which they were
+/// accepted (RFC 2033, Section 4.2).
+///
+/// This crate has no session layer, so this is only a helper for one: the
+/// server still tracks the transaction, calls [`LmtpRecipients::add`] and
+/// [`LmtpRecipients::clear`] at the right time and chooses each reply. The
+/// client side is [`LmtpResponseReceiver`](crate::response::parser::LmtpResponseReceiver).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LmtpRecipients<T> {
+    recipients: Vec<T>,
+}
+
+impl<T> Default for LmtpRecipients<T> {
+    fn default() -> Self {
+        Self {
+            recipients: Vec::new(),
         }
     }
+}
 
-    pub fn explain_status_code(&self) -> Option<(&'static str, &'static str)> {
-        match (self.esc[1], self.esc[2]) {
-            (0, 0) => (
-                "Other undefined Status",
-                concat!(
-                    "Other undefined status is the only undefined error",
-                    " code."
-                ),
-            )
-                .into(),
-            (1, 0) => (
-                "Other address status",
-                concat!(
-                    "Something about the address specified in the messa",
-                    "ge caused this DSN."
-                ),
-            )
-                .into(),
-            (1, 1) => (
-                "Bad destination mailbox address",
-                concat!(
-                    "The mailbox specified in the address does not exis",
-                    "t. For Internet mail names, this means the address",
-                    " portion to the left of the \"@\" sign is invalid.",
-                    " This code is only useful for permanent failures."
-                ),
-            )
-                .into(),
-            (1, 2) => (
-                "Bad destination system address",
-                concat!(
-                    "The destination system specified in the address do",
-                    "es not exist or is incapable of accepting mail. Fo",
-                    "r Internet mail names, this means the address port",
-                    "ion to the right of the \"@\" is invalid for mail.",
-                    " This code is only useful for permanent failures."
-                ),
-            )
-                .into(),
-            (1, 3) => (
-                "Bad destination mailbox address syntax",
-                concat!(
-                    "The destination address was syntactically invalid.",
-                    " This can apply to any field in the address. This ",
-                    "code is only useful for permanent failures."
-                ),
-            )
-                .into(),
-            (1, 4) => (
-                "Destination mailbox address ambiguous",
-                concat!(
-                    "The mailbox address as specified matches one or mo",
-                    "re recipients on the destination system. This may ",
-                    "result if a heuristic address mapping algorithm is",
-                    " used to map the specified address to a local mail",
-                    "box name."
-                ),
-            )
-                .into(),
-            (1, 5) => (
-                "Destination address valid",
-                concat!(
-                    "This mailbox address as specified was valid. This ",
-                    "status code should be used for positive delivery r",
-                    "eports."
-                ),
-            )
-                .into(),
-            (1, 6) => (
-                "Destination mailbox has moved, No forwarding address",
-                concat!(
-                    "The mailbox address provided was at one time valid",
-                    ", but mail is no longer being accepted for that ad",
-                    "dress. This code is only useful for permanent fail",
-                    "ures."
-                ),
-            )
-                .into(),
-            (1, 7) => (
-                "Bad sender's mailbox address syntax",
-                concat!(
-                    "The sender's address was syntactically invalid. Th",
-                    "is can apply to any field in the address."
-                ),
-            )
-                .into(),
-            (1, 8) => (
-                "Bad sender's system address",
-                concat!(
-                    "The sender's system specified in the address does ",
-                    "not exist or is incapable of accepting return mail",
-                    ". For domain names, this means the address portion",
-                    " to the right of the \"@\" is invalid for mail."
-                ),
-            )
-                .into(),
-            (1, 9) => (
-                "Message relayed to non-compliant mailer",
-                concat!(
-                    "The mailbox address specified was valid, but the m",
-                    "essage has been relayed to a system that does not ",
-                    "speak this protocol; no further information can be",
-                    " provided."
-                ),
-            )
-                .into(),
-            (1, 10) => (
-                "Recipient address has null MX",
-                concat!(
-                    "This status code is returned when the associated a",
-                    "ddress is marked as invalid using a null MX."
-                ),
-            )
-                .into(),
-            (2, 0) => (
-                "Other or undefined mailbox status",
-                concat!(
-                    "The mailbox exists, but something about the destin",
-                    "ation mailbox has caused the sending of this DSN."
-                ),
-            )
-                .into(),
-            (2, 1) => (
-                "Mailbox disabled, not accepting messages",
-                concat!(
-                    "The mailbox exists, but is not accepting messages.",
-                    " This may be a permanent error if the mailbox will",
-                    " never be re-enabled or a transient error if the m",
-                    "ailbox is only temporarily disabled."
-                ),
-            )
-                .into(),
-            (2, 2) => (
-                "Mailbox full",
-                concat!(
-                    "The mailbox is full because the user has exceeded ",
-                    "a per-mailbox administrative quota or physical cap",
-                    "acity. The general semantics implies that the reci",
-                    "pient can delete messages to make more space avail",
-                    "able. This code should be used as a persistent tra",
-                    "nsient failure."
-                ),
-            )
-                .into(),
-            (2, 3) => (
-                "Message length exceeds administrative limit",
-                concat!(
-                    "A per-mailbox administrative message length limit ",
-                    "has been exceeded. This status code should be used",
-                    " when the per-mailbox message length limit is less",
-                    " than the general system limit. This code should b",
-                    "e used as a permanent failure."
-                ),
-            )
-                .into(),
-            (2, 4) => (
-                "Mailing list expansion problem",
-                concat!(
-                    "The mailbox is a mailing list address and the mail",
-                    "ing list was unable to be expanded. This code may ",
-                    "represent a permanent failure or a persistent tran",
-                    "sient failure."
-                ),
-            )
-                .into(),
-            (3, 0) => (
-                "Other or undefined mail system status",
-                concat!(
-                    "The destination system exists and normally accepts",
-                    " mail, but something about the system has caused t",
-                    "he generation of this DSN."
-                ),
-            )
-                .into(),
-            (3, 1) => (
-                "Mail system full",
-                concat!(
-                    "Mail system storage has been exceeded. The general",
-                    " semantics imply that the individual recipient may",
-                    " not be able to delete material to make room for a",
-                    "dditional messages."
-                ),
-            )
-                .into(),
-            (3, 2) => (
-                "System not accepting network messages",
-                concat!(
-                    "The host on which the mailbox is resident is not a",
-                    "ccepting messages. Examples of such conditions inc",
-                    "lude an imminent shutdown, excessive load, or syst",
-                    "em maintenance."
-                ),
-            )
-                .into(),
-            (3, 3) => (
-                "System not capable of selected features",
-                concat!(
-                    "Selected features specified for the message are no",
-                    "t supported by the destination system. This can oc",
-                    "cur in gateways when features from one domain cann",
-                    "ot be mapped onto the supported feature in another",
-                    "."
-                ),
-            )
-                .into(),
-            (3, 4) => (
-                "Message too big for system",
-                concat!(
-                    "The message is larger than per-message size limit.",
-                    " This limit may either be for physical or administ",
-                    "rative reasons."
-                ),
-            )
-                .into(),
-            (3, 5) => (
-                "System incorrectly configured",
-                concat!(
-                    "The system is not configured in a manner that will",
-                    " permit it to accept this message."
-                ),
-            )
-                .into(),
-            (3, 6) => (
-                "Requested priority was changed",
-                concat!(
-                    "The message was accepted for relay/delivery, but t",
-                    "he requested priority (possibly the implied defaul",
-                    "t) was not honoured. The human readable text after",
-                    " the status code contains the new priority, follow",
-                    "ed by SP (space) and explanatory human readable te",
-                    "xt."
-                ),
-            )
-                .into(),
-            (4, 0) => (
-                "Other or undefined network or routing status",
-                concat!(
-                    "Something went wrong with the networking, but it i",
-                    "s not clear what the problem is, or the problem ca",
-                    "nnot be well expressed with any of the other provi",
-                    "ded detail codes."
-                ),
-            )
-                .into(),
-            (4, 1) => (
-                "No answer from host",
-                concat!(
-                    "The outbound connection attempt was not answered, ",
-                    "because either the remote system was busy, or was ",
-                    "unable to take a call."
-                ),
-            )
-                .into(),
-            (4, 2) => (
-                "Bad connection",
-                concat!(
-                    "The outbound connection was established, but was u",
-                    "nable to complete the message transaction, either ",
-                    "because of time-out, or inadequate connection qual",
-                    "ity."
-                ),
-            )
-                .into(),
-            (4, 3) => (
-                "Directory server failure",
-                concat!(
-                    "The network system was unable to forward the messa",
-                    "ge, because a directory server was unavailable. Th",
-                    "e inability to connect to an Internet DNS server i",
-                    "s one example of the directory server failure erro",
-                    "r."
-                ),
-            )
-                .into(),
-            (4, 4) => (
-                "Unable to route",
-                concat!(
-                    "The mail system was unable to determine the next h",
-                    "op for the message because the necessary routing i",
-                    "nformation was unavailable from the directory serv",
-                    "er. A DNS lookup returning only an SOA (Start of A",
-                    "dministration) record for a domain name is one exa",
-                    "mple of the unable to route error."
-                ),
-            )
-                .into(),
-            (4, 5) => (
-                "Mail system congestion",
-                concat!(
-                    "The mail system was unable to deliver the message ",
-                    "because the mail system was congested."
-                ),
-            )
-                .into(),
-            (4, 6) => (
-                "Routing loop detected",
-                concat!(
-                    "A routing loop caused the message to be forwarded ",
-                    "too many times, either because of incorrect routin",
-                    "g tables or a user- forwarding loop."
-                ),
-            )
-                .into(),
-            (4, 7) => (
-                "Delivery time expired",
-                concat!(
-                    "The message was considered too old by the rejectin",
-                    "g system, either because it remained on that host ",
-                    "too long or because the time-to-live value specifi",
-                    "ed by the sender of the message was exceeded. If p",
-                    "ossible, the code for the actual problem found whe",
-                    "n delivery was attempted should be returned rather",
-                    " than this code."
-                ),
-            )
-                .into(),
-            (5, 0) => (
-                "Other or undefined protocol status",
-                concat!(
-                    "Something was wrong with the protocol necessary to",
-                    " deliver the message to the next hop and the probl",
-                    "em cannot be well expressed with any of the other ",
-                    "provided detail codes."
-                ),
-            )
-                .into(),
-            (5, 1) => (
-                "Invalid command",
-                concat!(
-                    "A mail transaction protocol command was issued whi",
-                    "ch was either out of sequence or unsupported."
-                ),
-            )
-                .into(),
-            (5, 2) => (
-                "Syntax error",
-                concat!(
-                    "A mail transaction protocol command was issued whi",
-                    "ch could not be interpreted, either because the sy",
-                    "ntax was wrong or the command is unrecognized."
-                ),
-            )
-                .into(),
-            (5, 3) => (
-                "Too many recipients",
-                concat!(
-                    "More recipients were specified for the message tha",
-                    "n could have been delivered by the protocol. This ",
-                    "error should normally result in the segmentation o",
-                    "f the message into two, the remainder of the recip",
-                    "ients to be delivered on a subsequent delivery att",
-                    "empt. It is included in this list in the event tha",
-                    "t such segmentation is not possible."
-                ),
-            )
-                .into(),
-            (5, 4) => (
-                "Invalid command arguments",
-                concat!(
-                    "A valid mail transaction protocol command was issu",
-                    "ed with invalid arguments, either because the argu",
-                    "ments were out of range or represented unrecognize",
-                    "d features."
-                ),
-            )
-                .into(),
-            (5, 5) => (
-                "Wrong protocol version",
-                concat!(
-                    "A protocol version mis-match existed which could n",
-                    "ot be automatically resolved by the communicating ",
-                    "parties."
-                ),
-            )
-                .into(),
-            (5, 6) => (
-                "Authentication Exchange line is too long",
-                concat!(
-                    "This enhanced status code SHOULD be returned when ",
-                    "the server fails the AUTH command due to the clien",
-                    "t sending a [BASE64] response which is longer than",
-                    " the maximum buffer size available for the current",
-                    "ly selected SASL mechanism."
-                ),
-            )
-                .into(),
-            (6, 0) => (
-                "Other or undefined media error",
-                concat!(
-                    "Something about the content of a message caused it",
-                    " to be considered undeliverable and the problem ca",
-                    "nnot be well expressed with any of the other provi",
-                    "ded detail codes."
-                ),
-            )
-                .into(),
-            (6, 1) => (
-                "Media not supported",
-                concat!(
-                    "The media of the message is not supported by eithe",
-                    "r the delivery protocol or the next system in the ",
-                    "forwarding path."
-                ),
-            )
-                .into(),
-            (6, 2) => (
-                "Conversion required and prohibited",
-                concat!(
-                    "The content of the message must be converted befor",
-                    "e it can be delivered and such conversion is not p",
-                    "ermitted. Such prohibitions may be the expression ",
-                    "of the sender in the message itself or the policy ",
-                    "of the sending host."
-                ),
-            )
-                .into(),
-            (6, 3) => (
-                "Conversion required but not supported",
-                concat!(
-                    "The message content must be converted in order to ",
-                    "be forwarded but such conversion is not possible o",
-                    "r is not practical by a host in the forwarding pat",
-                    "h. This condition may result when an ESMTP gateway",
-                    " supports 8bit transport but is not able to downgr",
-                    "ade the message to 7 bit as required for the next ",
-                    "hop."
-                ),
-            )
-                .into(),
-            (6, 4) => (
-                "Conversion with loss performed",
-                concat!(
-                    "This is a warning sent to the sender when message ",
-                    "delivery was successfully but when the delivery re",
-                    "quired a conversion in which some data was lost. T",
-                    "his may also be a permanent error if the sender ha",
-                    "s indicated that conversion with loss is prohibite",
-                    "d for the message."
-                ),
-            )
-                .into(),
-            (6, 5) => (
-                "Conversion Failed",
-                concat!(
-                    "A conversion was required but was unsuccessful. Th",
-                    "is may be useful as a permanent or persistent temp",
-                    "orary notification."
-                ),
-            )
-                .into(),
-            (6, 6) => (
-                "Message content not available",
-                concat!(
-                    "The message content could not be fetched from a re",
-                    "mote system. This may be useful as a permanent or ",
-                    "persistent temporary notification."
-                ),
-            )
-                .into(),
-            (6, 7) => (
-                "Non-ASCII addresses not permitted for that sender/recipient",
-                concat!(
-                    "This indicates the reception of a MAIL or RCPT com",
-                    "mand that non-ASCII addresses are not permitted"
-                ),
-            )
-                .into(),
-            (6, 8 | 10) => (
-                "UTF-8 string reply is required, but not permitted by the SMTP client",
-                concat!(
-                    "This indicates that a reply containing a UTF-8 str",
-                    "ing is required to show the mailbox name, but that",
-                    " form of response is not permitted by the SMTP cli",
-                    "ent."
-                ),
-            )
-                .into(),
-            (6, 9) => (
-                concat!(
-                    "UTF-8 header message cannot be transferred to ",
-                    "one or more recipients, so the message must be rejected"
-                ),
-                concat!(
-                    "This indicates that transaction failed after the f",
-                    "inal \".\" of the DATA command."
-                ),
-            )
-                .into(),
-            (7, 0) => (
-                "Other or undefined security status",
-                concat!(
-                    "Something related to security caused the message t",
-                    "o be returned, and the problem cannot be well expr",
-                    "essed with any of the other provided detail codes.",
-                    " This status code may also be used when the condit",
-                    "ion cannot be further described because of securit",
-                    "y policies in force."
-                ),
-            )
-                .into(),
-            (7, 1) => (
-                "Delivery not authorized, message refused",
-                concat!(
-                    "The sender is not authorized to send to the destin",
-                    "ation. This can be the result of per-host or per-r",
-                    "ecipient filtering. This memo does not discuss the",
-                    " merits of any such filtering, but provides a mech",
-                    "anism to report such."
-                ),
-            )
-                .into(),
-            (7, 2) => (
-                "Mailing list expansion prohibited",
-                concat!(
-                    "The sender is not authorized to send a message to ",
-                    "the intended mailing list."
-                ),
-            )
-                .into(),
-            (7, 3) => (
-                "Security conversion required but not possible",
-                concat!(
-                    "A conversion from one secure messaging protocol to",
-                    " another was required for delivery and such conver",
-                    "sion was not possible."
-                ),
-            )
-                .into(),
-            (7, 4) => (
-                "Security features not supported",
-                concat!(
-                    "A message contained security features such as secu",
-                    "re authentication that could not be supported on t",
-                    "he delivery protocol."
-                ),
-            )
-                .into(),
-            (7, 5) => (
-                "Cryptographic failure",
-                concat!(
-                    "A transport system otherwise authorized to validat",
-                    "e or decrypt a message in transport was unable to ",
-                    "do so because necessary information such as key wa",
-                    "s not available or such information was invalid."
-                ),
-            )
-                .into(),
-            (7, 6) => (
-                "Cryptographic algorithm not supported",
-                concat!(
-                    "A transport system otherwise authorized to validat",
-                    "e or decrypt a message was unable to do so because",
-                    " the necessary algorithm was not supported."
-                ),
-            )
-                .into(),
-            (7, 7) => (
-                "Message integrity failure",
-                concat!(
-                    "A transport system otherwise authorized to validat",
-                    "e a message was unable to do so because the messag",
-                    "e was corrupted or altered. This may be useful as ",
-                    "a permanent, transient persistent, or successful d",
-                    "elivery code."
-                ),
-            )
-                .into(),
-            (7, 8) => (
-                "Authentication credentials invalid",
-                concat!(
-                    "This response to the AUTH command indicates that t",
-                    "he authentication failed due to invalid or insuffi",
-                    "cient authentication credentials. In this case, th",
-                    "e client SHOULD ask the user to supply new credent",
-                    "ials (such as by presenting a password dialog box)",
-                    "."
-                ),
-            )
-                .into(),
-            (7, 9) => (
-                "Authentication mechanism is too weak",
-                concat!(
-                    "This response to the AUTH command indicates that t",
-                    "he selected authentication mechanism is weaker tha",
-                    "n server policy permits for that user. The client ",
-                    "SHOULD retry with a new authentication mechanism."
-                ),
-            )
-                .into(),
-            (7, 10) => (
-                "Encryption Needed",
-                concat!(
-                    "This indicates that external strong privacy layer ",
-                    "is needed in order to use the requested authentica",
-                    "tion mechanism. This is primarily intended for use",
-                    " with clear text authentication mechanisms. A clie",
-                    "nt which receives this may activate a security lay",
-                    "er such as TLS prior to authenticating, or attempt",
-                    " to use a stronger mechanism."
-                ),
-            )
-                .into(),
-            (7, 11) => (
-                "Encryption required for requested authentication mechanism",
-                concat!(
-                    "This response to the AUTH command indicates that t",
-                    "he selected authentication mechanism may only be u",
-                    "sed when the underlying SMTP connection is encrypt",
-                    "ed. Note that this response code is documented her",
-                    "e for historical purposes only. Modern implementat",
-                    "ions SHOULD NOT advertise mechanisms that are not ",
-                    "permitted due to lack of encryption, unless an enc",
-                    "ryption layer of sufficient strength is currently ",
-                    "being employed."
-                ),
-            )
-                .into(),
-            (7, 12) => (
-                "A password transition is needed",
-                concat!(
-                    "This response to the AUTH command indicates that t",
-                    "he user needs to transition to the selected authen",
-                    "tication mechanism. This is typically done by auth",
-                    "enticating once using the [PLAIN] authentication m",
-                    "echanism. The selected mechanism SHOULD then work ",
-                    "for authentications in subsequent sessions."
-                ),
-            )
-                .into(),
-            (7, 13) => (
-                "User Account Disabled",
-                concat!(
-                    "Sometimes a system administrator will have to disa",
-                    "ble a user's account (e.g., due to lack of payment",
-                    ", abuse, evidence of a break-in attempt, etc). Thi",
-                    "s error code occurs after a successful authenticat",
-                    "ion to a disabled account. This informs the client",
-                    " that the failure is permanent until the user cont",
-                    "acts their system administrator to get the account",
-                    " re-enabled. It differs from a generic authenticat",
-                    "ion failure where the client's best option is to p",
-                    "resent the passphrase entry dialog in case the use",
-                    "r simply mistyped their passphrase."
-                ),
-            )
-                .into(),
-            (7, 14) => (
-                "Trust relationship required",
-                concat!(
-                    "The submission server requires a configured trust ",
-                    "relationship with a third-party server in order to",
-                    " access the message content. This value replaces t",
-                    "he prior use of X.7.8 for this error condition. th",
-                    "ereby updating [RFC4468]."
-                ),
-            )
-                .into(),
-            (7, 15) => (
-                "Priority Level is too low",
-                concat!(
-                    "The specified priority level is below the lowest p",
-                    "riority acceptable for the receiving SMTP server. ",
-                    "This condition might be temporary, for example the",
-                    " server is operating in a mode where only higher p",
-                    "riority messages are accepted for transfer and del",
-                    "ivery, while lower priority messages are rejected."
-                ),
-            )
-                .into(),
-            (7, 16) => (
-                "Message is too big for the specified priority",
-                concat!(
-                    "The message is too big for the specified priority.",
-                    " This condition might be temporary, for example th",
-                    "e server is operating in a mode where only higher ",
-                    "priority messages below certain size are accepted ",
-                    "for transfer and delivery."
-                ),
-            )
-                .into(),
-            (7, 17) => (
-                "Mailbox owner has changed",
-                concat!(
-                    "This status code is returned when a message is rec",
-                    "eived with a Require-Recipient-Valid-Since field o",
-                    "r RRVS extension and the receiving system is able ",
-                    "to determine that the intended recipient mailbox h",
-                    "as not been under continuous ownership since the s",
-                    "pecified date-time."
-                ),
-            )
-                .into(),
-            (7, 18) => (
-                "Domain owner has changed",
-                concat!(
-                    "This status code is returned when a message is rec",
-                    "eived with a Require-Recipient-Valid-Since field o",
-                    "r RRVS extension and the receiving system wishes t",
-                    "o disclose that the owner of the domain name of th",
-                    "e recipient has changed since the specified date-t",
-                    "ime."
-                ),
-            )
-                .into(),
-            (7, 19) => (
-                "RRVS test cannot be completed",
-                concat!(
-                    "This status code is returned when a message is rec",
-                    "eived with a Require-Recipient-Valid-Since field o",
-                    "r RRVS extension and the receiving system cannot c",
-                    "omplete the requested evaluation because the requi",
-                    "red timestamp was not recorded. The message origin",
-                    "ator needs to decide whether to reissue the messag",
-                    "e without RRVS protection."
-                ),
-            )
-                .into(),
-            (7, 20) => (
-                "No passing DKIM signature found",
-                concat!(
-                    "This status code is returned when a message did no",
-                    "t contain any passing DKIM signatures. (This viola",
-                    "tes the advice of Section 6.1 of [RFC6376].)"
-                ),
-            )
-                .into(),
-            (7, 21) => (
-                "No acceptable DKIM signature found",
-                concat!(
-                    "This status code is returned when a message contai",
-                    "ns one or more passing DKIM signatures, but none a",
-                    "re acceptable. (This violates the advice of Sectio",
-                    "n 6.1 of [RFC6376].)"
-                ),
-            )
-                .into(),
-            (7, 22) => (
-                "No valid author-matched DKIM signature found",
-                concat!(
-                    "This status code is returned when a message contai",
-                    "ns one or more passing DKIM signatures, but none a",
-                    "re acceptable because none have an identifier(s) t",
-                    "hat matches the author address(es) found in the Fr",
-                    "om header field. This is a special case of X.7.21.",
-                    " (This violates the advice of Section 6.1 of [RFC6",
-                    "376].)"
-                ),
-            )
-                .into(),
-            (7, 23) => (
-                "SPF validation failed",
-                concat!(
-                    "This status code is returned when a message comple",
-                    "ted an SPF check that produced a \"fail\" result, ",
-                    "contrary to local policy requirements. Used in pla",
-                    "ce of 5.7.1 as described in Section 8.4 of [RFC720",
-                    "8]."
-                ),
-            )
-                .into(),
-            (7, 24) => (
-                "SPF validation error",
-                concat!(
-                    "This status code is returned when evaluation of SP",
-                    "F relative to an arriving message resulted in an e",
-                    "rror. Used in place of 4.4.3 or 5.5.2 as described",
-                    " in Sections 8.6 and 8.7 of [RFC7208]."
-                ),
-            )
-                .into(),
-            (7, 25) => (
-                "Reverse DNS validation failed",
-                concat!(
-                    "This status code is returned when an SMTP client's",
-                    " IP address failed a reverse DNS validation check,",
-                    " contrary to local policy requirements."
-                ),
-            )
-                .into(),
-            (7, 26) => (
-                "Multiple authentication checks failed",
-                concat!(
-                    "This status code is returned when a message failed",
-                    " more than one message authentication check, contr",
-                    "ary to local policy requirements. The particular m",
-                    "echanisms that failed are not specified."
-                ),
-            )
-                .into(),
-            (7, 27) => (
-                "Sender address has null MX",
-                concat!(
-                    "This status code is returned when the associated s",
-                    "ender address has a null MX, and the SMTP receiver",
-                    " is configured to reject mail from such sender (e.",
-                    "g., because it could not return a DSN)."
-                ),
-            )
-                .into(),
-            (7, 28) => (
-                "Mail flood detected",
-                concat!(
-                    "The message appears to be part of a mail flood of ",
-                    "similar abusive messages."
-                ),
-            )
-                .into(),
-            (7, 29) => (
-                "ARC validation failure",
-                concat!(
-                    "This status code may be returned when a message fa",
-                    "ils ARC validation."
-                ),
-            )
-                .into(),
-            (7, 30) => (
-                "REQUIRETLS support required",
-                concat!(
-                    "This indicates that the message was not able to be",
-                    " forwarded because it was received with a REQUIRET",
-                    "LS requirement and none of the SMTP servers to whi",
-                    "ch the message should be forwarded provide this su",
-                    "pport."
-                ),
-            )
-                .into(),
-            _ => None,
+impl<T> LmtpRecipients<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a recipient if `response`, the reply to its `RCPT` command,
+    /// accepted it.
+    pub fn add(&mut self, recipient: T, response: &Response<impl Display>) {
+        if response.is_positive_completion() {
+            self.recipients.push(recipient);
+        }
+    }
+
+    pub fn recipients(&self) -> &[T] {
+        &self.recipients
+    }
+
+    /// Returns the number of replies to send after the message data. `DATA`
+    /// must be rejected with 503 when it is zero.
+    pub fn len(&self) -> usize {
+        self.recipients.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recipients.is_empty()
+    }
+
+    /// Forgets the recipients at the end of the transaction or on `RSET`.
+    pub fn clear(&mut self) {
+        self.recipients.clear();
+    }
+
+    /// Writes the reply returned by `reply` for each recipient after the end of
+    /// `DATA` or the last `BDAT`. A message that was rejected as a whole, for
+    /// instance with [`Error::MessageTooLarge`], still needs one reply for
+    /// each recipient.
+    pub fn write_responses<R: Display>(
+        &self,
+        mut writer: impl Write,
+        mut reply: impl FnMut(&T) -> Response<R>,
+    ) -> io::Result<()> {
+        for recipient in &self.recipients {
+            reply(recipient).write(&mut writer)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn write_ehlo() {
+        for (response, expected) in [
+            (
+                EhloResponse::new("mx.example.org"),
+                "250 mx.example.org you had me at EHLO\r\n",
+            ),
+            (
+                EhloResponse::new("mx.example.org").with_greeting("at your service"),
+                "250 mx.example.org at your service\r\n",
+            ),
+            (
+                EhloResponse::new("mx.example.org").with_greeting(""),
+                "250 mx.example.org\r\n",
+            ),
+            (
+                EhloResponse {
+                    capabilities: EXT_PIPELINING | EXT_SIZE | EXT_START_TLS,
+                    size: 1000,
+                    ..EhloResponse::new("mx.example.org")
+                },
+                concat!(
+                    "250-mx.example.org you had me at EHLO\r\n",
+                    "250-STARTTLS\r\n",
+                    "250-SIZE 1000\r\n",
+                    "250 PIPELINING\r\n",
+                ),
+            ),
+            (
+                {
+                    let mut response = EhloResponse::new("mx.example.org").with_greeting("hi");
+                    response.add_capability(EXT_PIPELINING);
+                    response.add_capability(EXT_SIZE | EXT_8BIT_MIME);
+                    response.add_capability(EXT_START_TLS);
+                    response.add_capability(EXT_PIPELINING);
+                    response.capabilities |= EXT_DSN | EXT_CHUNKING;
+                    response
+                },
+                concat!(
+                    "250-mx.example.org hi\r\n",
+                    "250-PIPELINING\r\n",
+                    "250-8BITMIME\r\n",
+                    "250-SIZE\r\n",
+                    "250-STARTTLS\r\n",
+                    "250-DSN\r\n",
+                    "250 CHUNKING\r\n",
+                ),
+            ),
+        ] {
+            let mut buf = Vec::new();
+            response.write(&mut buf).unwrap();
+            assert_eq!(expected, String::from_utf8(buf).unwrap());
+        }
+    }
+
+    #[test]
+    fn write_response() {
+        let long_word = "x".repeat(600);
+        let long_text = "lorem ipsum ".repeat(50);
+
+        for (response, expected) in [
+            (
+                Response::new(250, 2, 1, 0, "Sender OK".to_string()),
+                "250 2.1.0 Sender OK\r\n".to_string(),
+            ),
+            (
+                Response::new_basic(220, "mx.example.org ESMTP".to_string()),
+                "220 mx.example.org ESMTP\r\n".to_string(),
+            ),
+            (
+                Response::new(221, 0, 0, 0, "Bye\nSee you".to_string()),
+                "221-Bye\r\n221 See you\r\n".to_string(),
+            ),
+            (
+                Response::new(
+                    550,
+                    5,
+                    7,
+                    1,
+                    "Forwarding disabled\nSelect another host\r\n".to_string(),
+                ),
+                concat!(
+                    "550-5.7.1 Forwarding disabled\r\n",
+                    "550 5.7.1 Select another host\r\n"
+                )
+                .to_string(),
+            ),
+            (
+                Response::new(214, 2, 0, 0, "Commands:\r\n\r\n  HELO EHLO".to_string()),
+                concat!(
+                    "214-2.0.0 Commands:\r\n",
+                    "214-2.0.0 \r\n",
+                    "214 2.0.0   HELO EHLO\r\n"
+                )
+                .to_string(),
+            ),
+            (
+                Response::new(554, 5, 0, 0, long_word.clone()),
+                format!(
+                    "554-5.0.0 {}\r\n554 5.0.0 {}\r\n",
+                    &long_word[..500],
+                    &long_word[500..]
+                ),
+            ),
+            (
+                Response::new(554, 5, 0, 0, long_text.clone()),
+                format!(
+                    "554-5.0.0 {}\r\n554 5.0.0 {}\r\n",
+                    &long_text[..497],
+                    &long_text[498..]
+                ),
+            ),
+        ] {
+            let mut buf = Vec::new();
+            response.write(&mut buf).unwrap();
+            let written = String::from_utf8(buf).unwrap();
+            assert_eq!(expected, written);
+            assert!(
+                written
+                    .split_inclusive("\r\n")
+                    .all(|line| line.len() <= super::MAX_REPLY_LINE_LENGTH)
+            );
+        }
+    }
+
+    #[test]
+    fn error_to_response() {
+        for (request, expected) in [
+            ("FOOBAR", "500 5.5.1 Command not recognized\r\n"),
+            (
+                "MAIL FROM:<@invalid>",
+                "501 5.1.7 Bad sender's mailbox address syntax\r\n",
+            ),
+            (
+                "RCPT TO:<hi..there@invalid.org>",
+                "501 5.1.3 Bad destination mailbox address syntax\r\n",
+            ),
+            ("EHLO", "501 5.5.2 Syntax error, expected: EHLO domain\r\n"),
+            (
+                "MAIL FROM:<> SIZE=ABC",
+                "501 5.5.4 Invalid value for parameter SIZE\r\n",
+            ),
+            (
+                "RCPT TO:<a@b> X-PARAM=1",
+                "555 5.5.4 Unsupported parameter X-PARAM\r\n",
+            ),
+            (
+                "MAIL FROM:<a@b> FOO",
+                "555 5.5.4 Unsupported parameter FOO\r\n",
+            ),
+            (
+                "MAIL FROM:<a@b> SIZE=1 SIZE=2",
+                "501 5.5.4 Duplicate parameter SIZE\r\n",
+            ),
+        ] {
+            let request = format!("{request}\r\n");
+            let error = Request::parse(&mut request.as_bytes().iter()).unwrap_err();
+            let response = error.to_response();
+            let mut buf = Vec::new();
+            response.write(&mut buf).unwrap();
+            assert_eq!(expected, String::from_utf8(buf).unwrap());
+            assert!(
+                response
+                    .enhanced_status_code()
+                    .unwrap()
+                    .matches_reply_code(response.code)
+            );
+        }
+
+        for error in [
+            Error::NeedsMoreData { bytes_left: 0 },
+            Error::ResponseTooLong,
+            Error::InvalidResponse { code: 999 },
+            Error::MessageTooLarge,
+            Error::InvalidLineEnding {
+                ending: BareLineEnding::Lf,
+            },
+            Error::InvalidUtf8 {
+                violation: Utf8Violation::EightBitWithoutSmtpUtf8,
+                context: ErrorContext {
+                    verb: "MAIL",
+                    ..Default::default()
+                },
+            },
+            Error::InvalidUtf8 {
+                violation: Utf8Violation::NotNfc,
+                context: ErrorContext {
+                    verb: "RCPT",
+                    ..Default::default()
+                },
+            },
+            Error::InvalidUtf8 {
+                violation: Utf8Violation::EightBitWithoutSmtpUtf8,
+                context: ErrorContext {
+                    verb: "EHLO",
+                    ..Default::default()
+                },
+            },
+        ] {
+            let response = error.to_response();
+            assert!(
+                response
+                    .enhanced_status_code()
+                    .unwrap()
+                    .matches_reply_code(response.code)
+            );
+        }
+    }
+
+    #[test]
+    fn write_safe() {
+        for (message, expected) in [
+            (
+                "Unknown user <a@b>\r\n250 2.1.5 OK",
+                concat!(
+                    "550-5.1.1 Unknown user <a@b>\r\n",
+                    "550 5.1.1 250 2.1.5 OK\r\n"
+                ),
+            ),
+            (
+                "Unknown user <a@b>\n\r354 Go ahead\r",
+                concat!(
+                    "550-5.1.1 Unknown user <a@b>\r\n",
+                    "550-5.1.1 \r\n",
+                    "550 5.1.1 354 Go ahead\r\n"
+                ),
+            ),
+            (
+                "Unknown\0 user\x1b[2J\x7f\u{85}\t<a@b>",
+                "550 5.1.1 Unknown user[2J\t<a@b>\r\n",
+            ),
+        ] {
+            let mut buf = Vec::new();
+            Response::new(550, 5, 1, 1, message)
+                .write_safe(&mut buf)
+                .unwrap();
+            let written = String::from_utf8(buf).unwrap();
+            assert_eq!(expected, written);
+
+            // Every line must be a well-formed line of the same reply
+            let mut lines = written.split_terminator("\r\n").peekable();
+            while let Some(line) = lines.next() {
+                assert!(!line.contains(['\r', '\n']));
+                assert!(line.starts_with(if lines.peek().is_some() {
+                    "550-5.1.1 "
+                } else {
+                    "550 5.1.1 "
+                }));
+            }
+        }
+
+        let mut buf = Vec::new();
+        EhloResponse {
+            greeting: Some("hi\r\n250 AUTH PLAIN\r\n"),
+            capabilities: EXT_NO_SOLICITING,
+            no_soliciting: Some("org.example:ADV\r\n250 CHUNKING".to_string()),
+            ..EhloResponse::new("mx\n.example.org")
         }
+        .write_safe(&mut buf)
+        .unwrap();
+        assert_eq!(
+            concat!(
+                "250-mx.example.org hi250 AUTH PLAIN\r\n",
+                "250 NO-SOLICITING org.example:ADV250 CHUNKING\r\n",
+            ),
+            String::from_utf8(buf).unwrap()
+        );
     }
 }
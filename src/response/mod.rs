@@ -11,6 +11,8 @@ use crate::{EhloResponse, Response};
 
 pub mod generate;
 pub mod parser;
+pub mod received;
+pub mod status;
 
 define_tokens_128! {
     _8BITMIME = "8BITMIME",
@@ -72,11 +74,15 @@ impl<T: Display> EhloResponse<T> {
 
 impl<T: Display> Display for Response<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Code: {}, Enhanced code: {}.{}.{}, Message: {}",
-            self.code, self.esc[0], self.esc[1], self.esc[2], self.message,
-        )
+        if let Some(esc) = &self.esc {
+            write!(
+                f,
+                "Code: {}, Enhanced code: {}.{}.{}, Message: {}",
+                self.code, esc[0], esc[1], esc[2], self.message,
+            )
+        } else {
+            write!(f, "Code: {}, Message: {}", self.code, self.message)
+        }
     }
 }
 
@@ -86,10 +92,14 @@ where
     <T as rkyv::Archive>::Archived: std::fmt::Display,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Code: {}, Enhanced code: {}.{}.{}, Message: {}",
-            self.code, self.esc[0], self.esc[1], self.esc[2], self.message,
-        )
+        if let Some(esc) = self.esc.as_ref() {
+            write!(
+                f,
+                "Code: {}, Enhanced code: {}.{}.{}, Message: {}",
+                self.code, esc[0], esc[1], esc[2], self.message,
+            )
+        } else {
+            write!(f, "Code: {}, Message: {}", self.code, self.message)
+        }
     }
 }
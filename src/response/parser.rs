@@ -6,7 +6,10 @@
 
 use std::slice::Iter;
 
-use crate::{request::parser::Rfc5321Parser, *};
+use crate::{
+    request::parser::{Rfc5321Parser, into_utf8_lossy},
+    *,
+};
 
 use super::*;
 
@@ -16,7 +19,7 @@ pub const MAX_RESPONSE_LENGTH: usize = 4096;
 pub struct ResponseReceiver {
     buf: Vec<u8>,
     code: u16,
-    esc: [u8; 3],
+    esc: Option<[u8; 3]>,
     esc_cur: [u8; 3],
     esc_restore: [u8; 10],
     esc_pos: usize,
@@ -28,7 +31,7 @@ impl ResponseReceiver {
     pub fn from_code(code: u16) -> Self {
         Self {
             code,
-            esc: [0u8; 3],
+            esc: None,
             esc_cur: [0u8; 3],
             esc_restore: [0u8; 10],
             esc_pos: 0,
@@ -53,6 +56,7 @@ impl ResponseReceiver {
                     } else {
                         return Err(Error::SyntaxError {
                             syntax: "Invalid response code",
+                            context: ErrorContext::default(),
                         });
                     }
                 }
@@ -73,6 +77,7 @@ impl ResponseReceiver {
                     _ => {
                         return Err(Error::SyntaxError {
                             syntax: "Invalid response separator",
+                            context: ErrorContext::default(),
                         });
                     }
                 },
@@ -104,7 +109,7 @@ impl ResponseReceiver {
                             }
                         }
                         b' ' | b'\r' | b'\n' if self.pos == 6 => {
-                            self.esc = self.esc_cur;
+                            self.esc = Some(self.esc_cur);
                             self.esc_cur.fill(0);
                             self.pos = 7;
                             self.esc_pos = 0;
@@ -157,7 +162,7 @@ impl ResponseReceiver {
     pub fn reset(&mut self) {
         self.is_last = false;
         self.code = 0;
-        self.esc.fill(0);
+        self.esc = None;
         self.esc_cur.fill(0);
         self.pos = 0;
         self.esc_pos = 0;
@@ -165,6 +170,36 @@ impl ResponseReceiver {
     }
 }
 
+/// Collects the replies an LMTP server sends after the message data, one for
+/// each accepted recipient in the order of the `RCPT` commands (RFC 2033).
+pub struct LmtpResponseReceiver {
+    receiver: ResponseReceiver,
+    responses: Vec<Response<String>>,
+    recipients: usize,
+}
+
+impl LmtpResponseReceiver {
+    pub fn new(recipients: usize) -> Self {
+        Self {
+            receiver: ResponseReceiver::default(),
+            responses: Vec::with_capacity(recipients),
+            recipients,
+        }
+    }
+
+    /// Returns the replies once all of them have been received, or
+    /// [`Error::NeedsMoreData`] until then. Any bytes after the last reply
+    /// are left in `bytes`.
+    pub fn parse(&mut self, bytes: &mut Iter<'_, u8>) -> Result<Vec<Response<String>>, Error> {
+        while self.responses.len() < self.recipients {
+            let response = self.receiver.parse(bytes)?;
+            self.receiver.reset();
+            self.responses.push(response);
+        }
+        Ok(std::mem::take(&mut self.responses))
+    }
+}
+
 impl EhloResponse<String> {
     pub fn parse(bytes: &mut Iter<'_, u8>) -> Result<Self, Error> {
         let mut parser = Rfc5321Parser::new(bytes);
@@ -182,6 +217,7 @@ impl EhloResponse<String> {
                     _ => {
                         return Err(Error::SyntaxError {
                             syntax: "unexpected token",
+                            context: ErrorContext::default(),
                         });
                     }
                 }
@@ -202,6 +238,7 @@ impl EhloResponse<String> {
                 _ => {
                     return Err(Error::SyntaxError {
                         syntax: "unexpected token",
+                        context: ErrorContext::default(),
                     });
                 }
             }
@@ -292,7 +329,7 @@ impl EhloResponse<String> {
                         response.no_soliciting = if parser.stop_char != LF {
                             let text = parser.text()?;
                             if !text.is_empty() {
-                                text.into_owned().into()
+                                into_utf8_lossy(text).into_owned().into()
                             } else {
                                 None
                             }
@@ -353,7 +390,81 @@ impl EhloResponse<String> {
 mod tests {
     use crate::*;
 
-    use super::ResponseReceiver;
+    use super::{LmtpResponseReceiver, ResponseReceiver};
+    use crate::response::generate::LmtpRecipients;
+
+    #[test]
+    fn parse_lmtp_responses() {
+        let mut recipients = LmtpRecipients::new();
+        for (recipient, code) in [
+            ("pat@example.com", 250),
+            ("jones@example.com", 550),
+            ("green@example.com", 250),
+            ("smith@example.com", 250),
+        ] {
+            recipients.add(recipient, &Response::new(code, 0, 0, 0, "ok"));
+        }
+        assert_eq!(
+            recipients.recipients(),
+            ["pat@example.com", "green@example.com", "smith@example.com"]
+        );
+
+        let mut buf = Vec::new();
+        recipients
+            .write_responses(&mut buf, |&recipient| match recipient {
+                "green@example.com" => Response::new(
+                    452,
+                    4,
+                    2,
+                    2,
+                    format!("<{recipient}> is over quota,\ntry again later"),
+                ),
+                _ => Response::new(250, 2, 1, 5, format!("<{recipient}> delivered")),
+            })
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buf.clone()).unwrap(),
+            concat!(
+                "250 2.1.5 <pat@example.com> delivered\r\n",
+                "452-4.2.2 <green@example.com> is over quota,\r\n",
+                "452 4.2.2 try again later\r\n",
+                "250 2.1.5 <smith@example.com> delivered\r\n",
+            )
+        );
+        buf.extend_from_slice(b"221 2.0.0 Bye\r\n");
+
+        for chunk_size in [1, 7, buf.len()] {
+            let mut receiver = LmtpResponseReceiver::new(recipients.len());
+            let mut chunks = buf.chunks(chunk_size);
+            let (responses, rest) = loop {
+                let mut bytes = chunks.next().unwrap().iter();
+                match receiver.parse(&mut bytes) {
+                    Ok(responses) => break (responses, bytes.as_slice()),
+                    Err(Error::NeedsMoreData { .. }) => assert_eq!(bytes.len(), 0),
+                    Err(err) => panic!("Unexpected error {err:?}"),
+                }
+            };
+            assert_eq!(
+                responses
+                    .iter()
+                    .map(|response| (response.code, response.message.as_str()))
+                    .collect::<Vec<_>>(),
+                [
+                    (250, "<pat@example.com> delivered"),
+                    (452, "<green@example.com> is over quota,\ntry again later"),
+                    (250, "<smith@example.com> delivered"),
+                ],
+                "{chunk_size}"
+            );
+            let rest = [rest, &chunks.flatten().copied().collect::<Vec<_>>()].concat();
+            assert_eq!(rest, b"221 2.0.0 Bye\r\n", "{chunk_size}");
+        }
+
+        assert_eq!(
+            LmtpResponseReceiver::new(0).parse(&mut b"".iter()),
+            Ok(vec![])
+        );
+    }
 
     #[test]
     fn parse_ehlo() {
@@ -390,6 +501,7 @@ mod tests {
                 ),
                 Ok(EhloResponse {
                     hostname: "dbc.mtview.ca.us".to_string(),
+                    greeting: None,
                     capabilities: EXT_8BIT_MIME
                         | EXT_ATRN
                         | EXT_AUTH
@@ -416,6 +528,7 @@ mod tests {
                         | EXT_SIZE
                         | EXT_SMTP_UTF8
                         | EXT_START_TLS,
+                    capability_order: vec![],
                     auth_mechanisms: AUTH_GSSAPI | AUTH_DIGEST_MD5 | AUTH_PLAIN,
                     deliver_by: 0,
                     future_release_interval: 1234,
@@ -437,12 +550,14 @@ mod tests {
                 ),
                 Ok(EhloResponse {
                     hostname: "".to_string(),
+                    greeting: None,
                     capabilities: EXT_DELIVER_BY
                         | EXT_FUTURE_RELEASE
                         | EXT_MT_PRIORITY
                         | EXT_NO_SOLICITING
                         | EXT_SIZE
                         | EXT_SMTP_UTF8,
+                    capability_order: vec![],
                     auth_mechanisms: 0,
                     deliver_by: 240,
                     future_release_interval: 123,
@@ -460,7 +575,9 @@ mod tests {
                 ),
                 Ok(EhloResponse {
                     hostname: "dbc.mtview.ca.us".to_string(),
+                    greeting: None,
                     capabilities: EXT_FUTURE_RELEASE | EXT_MT_PRIORITY,
+                    capability_order: vec![],
                     auth_mechanisms: 0,
                     deliver_by: 0,
                     future_release_interval: 0,
@@ -502,7 +619,7 @@ mod tests {
                 "250 2.1.1 Originator <ned@ymir.claremont.edu> ok\n",
                 Response {
                     code: 250,
-                    esc: [2, 1, 1],
+                    esc: Some([2, 1, 1]),
                     message: "Originator <ned@ymir.claremont.edu> ok".to_string(),
                 },
                 true,
@@ -514,7 +631,7 @@ mod tests {
                 ),
                 Response {
                     code: 551,
-                    esc: [5, 7, 1],
+                    esc: Some([5, 7, 1]),
                     message: concat!(
                         "Forwarding to remote hosts disabled\n",
                         "Select another host to act as your forwarder"
@@ -530,7 +647,7 @@ mod tests {
                 ),
                 Response {
                     code: 550,
-                    esc: [0, 0, 0],
+                    esc: None,
                     message: "mailbox unavailable\nuser has moved with no forwarding address"
                         .to_string(),
                 },
@@ -543,7 +660,7 @@ mod tests {
                 ),
                 Response {
                     code: 550,
-                    esc: [0, 0, 0],
+                    esc: None,
                     message: "mailbox unavailable\nuser has moved with no forwarding address"
                         .to_string(),
                 },
@@ -564,7 +681,7 @@ mod tests {
                 ),
                 Response {
                     code: 432,
-                    esc: [6, 8, 9],
+                    esc: Some([6, 8, 9]),
                     message: "\nHello\n\n,\n\n\n6\n\n6.\n\n6.8\n\n\nWorld!".to_string(),
                 },
                 true,
@@ -573,7 +690,7 @@ mod tests {
                 "250 2address.org\n",
                 Response {
                     code: 250,
-                    esc: [0, 0, 0],
+                    esc: None,
                     message: "2address.org".to_string(),
                 },
                 true,
@@ -582,7 +699,7 @@ mod tests {
                 "250 100.address.org\n",
                 Response {
                     code: 250,
-                    esc: [0, 0, 0],
+                    esc: None,
                     message: "100.address.org".to_string(),
                 },
                 true,
@@ -591,7 +708,7 @@ mod tests {
                 "250 111111111111\n",
                 Response {
                     code: 250,
-                    esc: [0, 0, 0],
+                    esc: None,
                     message: "111111111111".to_string(),
                 },
                 true,
@@ -600,7 +717,7 @@ mod tests {
                 "250 99999999999999\n",
                 Response {
                     code: 250,
-                    esc: [0, 0, 0],
+                    esc: None,
                     message: "99999999999999".to_string(),
                 },
                 true,
@@ -609,7 +726,7 @@ mod tests {
                 "250 2.0.0 Message queued for delivery.\r\n",
                 Response {
                     code: 250,
-                    esc: [2, 0, 0],
+                    esc: Some([2, 0, 0]),
                     message: "Message queued for delivery.".to_string(),
                 },
                 true,
@@ -618,7 +735,7 @@ mod tests {
                 concat!("250-Missing space\n", "250\n", "250 Ignore this"),
                 Response {
                     code: 250,
-                    esc: [0, 0, 0],
+                    esc: None,
                     message: "Missing space\n".to_string(),
                 },
                 true,
@@ -0,0 +1,365 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs LLC <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: Apache-2.0 OR MIT
+ */
+
+use std::{
+    fmt::Display,
+    io::{self, Write},
+    net::IpAddr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{Greeting, Received, Request, response::generate::sanitize};
+
+impl<T: Display> Received<T> {
+    /// Creates a header for a message received now, in UTC.
+    pub fn new(hostname: T) -> Self {
+        Self {
+            hostname,
+            greeting: None,
+            peer_ip: None,
+            peer_hostname: None,
+            tls: false,
+            auth: false,
+            smtp_utf8: false,
+            id: None,
+            recipient: None,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs() as i64),
+            utc_offset: 0,
+        }
+    }
+
+    /// Takes the client domain from an `EHLO`, `HELO` or `LHLO` request. Other
+    /// requests are ignored.
+    pub fn with_greeting(mut self, request: &Request<T>) -> Self
+    where
+        T: Clone,
+    {
+        match request {
+            Request::Helo { host } => self.greeting = Some((Greeting::Helo, host.clone())),
+            Request::Ehlo { host } => self.greeting = Some((Greeting::Ehlo, host.clone())),
+            Request::Lhlo { host } => self.greeting = Some((Greeting::Lhlo, host.clone())),
+            _ => (),
+        }
+        self
+    }
+
+    /// Sets the address of the client and its reverse DNS name, if known.
+    pub fn with_peer(mut self, ip: IpAddr, hostname: Option<T>) -> Self {
+        self.peer_ip = Some(ip);
+        self.peer_hostname = hostname;
+        self
+    }
+
+    pub fn with_tls(mut self, tls: bool) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    pub fn with_auth(mut self, auth: bool) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    pub fn with_smtp_utf8(mut self, smtp_utf8: bool) -> Self {
+        self.smtp_utf8 = smtp_utf8;
+        self
+    }
+
+    pub fn with_id(mut self, id: T) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// Sets the envelope recipient, which should only be disclosed when the
+    /// message has a single one.
+    pub fn with_recipient(mut self, recipient: T) -> Self {
+        self.recipient = Some(recipient);
+        self
+    }
+
+    /// Sets the time the message was received, as seconds since the Unix epoch,
+    /// and the offset from UTC of the local time in minutes.
+    pub fn with_date(mut self, timestamp: i64, utc_offset: i32) -> Self {
+        self.timestamp = timestamp;
+        self.utc_offset = utc_offset;
+        self
+    }
+
+    /// Returns the `with` protocol keyword (RFC 3848 and RFC 6531).
+    pub fn protocol(&self) -> String {
+        let mut protocol = match (
+            self.greeting.as_ref().map(|(greeting, _)| greeting),
+            self.smtp_utf8,
+        ) {
+            (Some(Greeting::Ehlo), false) => "ESMTP",
+            (Some(Greeting::Ehlo), true) => "UTF8SMTP",
+            (Some(Greeting::Lhlo), false) => "LMTP",
+            (Some(Greeting::Lhlo), true) => "UTF8LMTP",
+            // Extensions are not available after HELO
+            (Some(Greeting::Helo) | None, _) => return "SMTP".to_string(),
+        }
+        .to_string();
+        if self.tls {
+            protocol.push('S');
+        }
+        if self.auth {
+            protocol.push('A');
+        }
+        protocol
+    }
+
+    /// Writes the header, folded over several lines of at most 78 characters
+    /// where possible and ending with CRLF. Control characters are removed from
+    /// all values.
+    pub fn write(&self, writer: impl Write) -> io::Result<()> {
+        let peer_ip = self.peer_ip.map(|ip| match ip {
+            IpAddr::V4(ip) => format!("[{ip}]"),
+            IpAddr::V6(ip) => format!("[IPv6:{ip}]"),
+        });
+        let mut writer = FoldingWriter { writer, column: 0 };
+
+        writer.write_str("Received:")?;
+        writer.write_word("from")?;
+        match (&self.greeting, &peer_ip) {
+            (Some((_, host)), _) => writer.write_word(&sanitize(&host.to_string(), false))?,
+            (None, Some(peer_ip)) => writer.write_word(peer_ip)?,
+            (None, None) => writer.write_word("unknown")?,
+        }
+        if let Some(peer_ip) = &peer_ip {
+            match &self.peer_hostname {
+                Some(peer_hostname) => {
+                    writer
+                        .write_word(&format!("({}", sanitize(&peer_hostname.to_string(), false)))?;
+                    writer.write_word(&format!("{peer_ip})"))?;
+                }
+                None => writer.write_word(&format!("({peer_ip})"))?,
+            }
+        }
+
+        writer.fold()?;
+        writer.write_str("by")?;
+        writer.write_word(&sanitize(&self.hostname.to_string(), false))?;
+        writer.write_word("with")?;
+        writer.write_word(&self.protocol())?;
+        if let Some(id) = &self.id {
+            writer.write_word("id")?;
+            writer.write_word(&sanitize(&id.to_string(), false))?;
+        }
+        let mut date = Vec::with_capacity(31);
+        write_date(&mut date, self.timestamp, self.utc_offset)?;
+        let date = String::from_utf8_lossy(&date);
+        match &self.recipient {
+            Some(recipient) => {
+                writer.fold()?;
+                writer.write_str("for")?;
+                writer.write_word(&format!("<{}>;", sanitize(&recipient.to_string(), false)))?;
+                writer.write_word(&date)?;
+            }
+            None => {
+                writer.write_str(";")?;
+                writer.fold()?;
+                writer.write_str(&date)?;
+            }
+        }
+        writer.writer.write_all(b"\r\n")
+    }
+}
+
+/// Writes header words separated by spaces, folding the line before a word
+/// that would make it longer than 78 characters (RFC 5322, Section 2.1.1).
+struct FoldingWriter<W> {
+    writer: W,
+    column: usize,
+}
+
+impl<W: Write> FoldingWriter<W> {
+    fn write_str(&mut self, text: &str) -> io::Result<()> {
+        self.column += text.chars().count();
+        self.writer.write_all(text.as_bytes())
+    }
+
+    fn write_word(&mut self, word: &str) -> io::Result<()> {
+        if self.column > 1 && self.column + 1 + word.chars().count() > 78 {
+            self.fold()?;
+        } else {
+            self.write_str(" ")?;
+        }
+        self.write_str(word)
+    }
+
+    fn fold(&mut self) -> io::Result<()> {
+        self.column = 1;
+        self.writer.write_all(b"\r\n\t")
+    }
+}
+
+/// Writes a date in the RFC 5322 format, such as `Fri, 21 Nov 1997 09:55:06 -0600`.
+fn write_date(mut writer: impl Write, timestamp: i64, utc_offset: i32) -> io::Result<()> {
+    const DAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let local = timestamp + i64::from(utc_offset) * 60;
+    let days = local.div_euclid(86400);
+    let seconds = local.rem_euclid(86400);
+
+    // Civil date from the number of days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    write!(
+        writer,
+        "{}, {} {} {:04} {:02}:{:02}:{:02} {}{:02}{:02}",
+        DAYS[(days + 4).rem_euclid(7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        if utc_offset < 0 { '-' } else { '+' },
+        utc_offset.unsigned_abs() / 60,
+        utc_offset.unsigned_abs() % 60,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::write_date;
+    use crate::{Received, Request};
+
+    #[test]
+    fn received_header() {
+        for (timestamp, utc_offset, expected) in [
+            (0, 0, "Thu, 1 Jan 1970 00:00:00 +0000"),
+            (880127706, -360, "Fri, 21 Nov 1997 09:55:06 -0600"),
+            (1709164800, 60, "Thu, 29 Feb 2024 01:00:00 +0100"),
+            (951782399, 0, "Mon, 28 Feb 2000 23:59:59 +0000"),
+            (-1, 330, "Thu, 1 Jan 1970 05:29:59 +0530"),
+            (4102444800, -210, "Thu, 31 Dec 2099 20:30:00 -0330"),
+        ] {
+            let mut buf = Vec::new();
+            write_date(&mut buf, timestamp, utc_offset).unwrap();
+            assert_eq!(String::from_utf8(buf).unwrap(), expected, "{timestamp}");
+        }
+
+        for (greeting, smtp_utf8, tls, auth, expected) in [
+            ("HELO a", false, false, false, "SMTP"),
+            ("HELO a", true, true, true, "SMTP"),
+            ("EHLO a", false, false, false, "ESMTP"),
+            ("EHLO a", false, true, false, "ESMTPS"),
+            ("EHLO a", false, false, true, "ESMTPA"),
+            ("EHLO a", false, true, true, "ESMTPSA"),
+            ("EHLO a", true, true, false, "UTF8SMTPS"),
+            ("EHLO a", true, true, true, "UTF8SMTPSA"),
+            ("LHLO a", false, false, false, "LMTP"),
+            ("LHLO a", false, true, true, "LMTPSA"),
+            ("LHLO a", true, false, true, "UTF8LMTPA"),
+            ("NOOP", false, true, true, "SMTP"),
+        ] {
+            let line = format!("{greeting}\r\n");
+            let request = Request::parse(&mut line.as_bytes().iter()).unwrap();
+            assert_eq!(
+                Received::new("mx.example.org".into())
+                    .with_greeting(&request)
+                    .with_smtp_utf8(smtp_utf8)
+                    .with_tls(tls)
+                    .with_auth(auth)
+                    .protocol(),
+                expected,
+                "{greeting} {smtp_utf8} {tls} {auth}"
+            );
+        }
+
+        for (received, expected) in [
+            (
+                Received::new("mx.example.org")
+                    .with_greeting(&Request::Ehlo {
+                        host: "mail.example.com",
+                    })
+                    .with_peer(
+                        "192.0.2.1".parse::<IpAddr>().unwrap(),
+                        Some("mail.example.com"),
+                    )
+                    .with_tls(true)
+                    .with_id("4ABC1234")
+                    .with_recipient("jdoe@example.org")
+                    .with_date(880127706, -360),
+                concat!(
+                    "Received: from mail.example.com (mail.example.com [192.0.2.1])\r\n",
+                    "\tby mx.example.org with ESMTPS id 4ABC1234\r\n",
+                    "\tfor <jdoe@example.org>; Fri, 21 Nov 1997 09:55:06 -0600\r\n",
+                ),
+            ),
+            (
+                Received::new("mx.example.org")
+                    .with_greeting(&Request::Helo {
+                        host: "bad\r\nX-Injected: 1",
+                    })
+                    .with_peer("2001:db8::1".parse::<IpAddr>().unwrap(), None)
+                    .with_date(0, 0),
+                concat!(
+                    "Received: from badX-Injected: 1 ([IPv6:2001:db8::1])\r\n",
+                    "\tby mx.example.org with SMTP;\r\n",
+                    "\tThu, 1 Jan 1970 00:00:00 +0000\r\n",
+                ),
+            ),
+            (
+                Received::new("mx.example.org")
+                    .with_peer("192.0.2.1".parse::<IpAddr>().unwrap(), None)
+                    .with_auth(true)
+                    .with_date(0, 0),
+                concat!(
+                    "Received: from [192.0.2.1] ([192.0.2.1])\r\n",
+                    "\tby mx.example.org with SMTP;\r\n",
+                    "\tThu, 1 Jan 1970 00:00:00 +0000\r\n",
+                ),
+            ),
+            (
+                Received::new("mx.example.org\r\nX-Injected: 1")
+                    .with_greeting(&Request::Ehlo {
+                        host: "a-rather-long-client-hostname.subdomain.example.com",
+                    })
+                    .with_peer(
+                        "2001:db8:85a3::8a2e:370:7334".parse::<IpAddr>().unwrap(),
+                        Some("a-rather-long-reverse-dns-name.subdomain.example.com"),
+                    )
+                    .with_id("4ABC\n1234")
+                    .with_recipient("a.rather.long.recipient.address@subdomain.example.org")
+                    .with_date(0, 0),
+                concat!(
+                    "Received: from a-rather-long-client-hostname.subdomain.example.com\r\n",
+                    "\t(a-rather-long-reverse-dns-name.subdomain.example.com\r\n",
+                    "\t[IPv6:2001:db8:85a3::8a2e:370:7334])\r\n",
+                    "\tby mx.example.orgX-Injected: 1 with ESMTP id 4ABC1234\r\n",
+                    "\tfor <a.rather.long.recipient.address@subdomain.example.org>;\r\n",
+                    "\tThu, 1 Jan 1970 00:00:00 +0000\r\n",
+                ),
+            ),
+        ] {
+            let mut buf = Vec::new();
+            received.write(&mut buf).unwrap();
+            assert_eq!(String::from_utf8(buf).unwrap(), expected);
+            assert!(
+                expected.split("\r\n").all(|line| line.len() <= 78),
+                "{expected}"
+            );
+        }
+    }
+}
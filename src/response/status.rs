@@ -0,0 +1,1356 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs LLC <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: Apache-2.0 OR MIT
+ */
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::{EnhancedStatusCode, Error, ErrorContext};
+
+/// An entry of the IANA "Enumerated Status Codes" registry (RFC 3463, RFC 5248).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusCodeInfo {
+    pub subject: u8,
+    pub detail: u8,
+    pub title: &'static str,
+    pub description: &'static str,
+    /// Basic reply codes the status code is associated with, empty when the
+    /// registry does not list any.
+    pub reply_codes: &'static [u16],
+}
+
+/// Enhanced status code classes: class, title and description.
+pub static CLASS_CODES: &[(u8, &str, &str)] = &[
+    (
+        2,
+        "Success",
+        concat!(
+            "Success specifies that the DSN is reporting a posi",
+            "tive delivery action. Detail sub-codes may provide",
+            " notification of transformations required for deli",
+            "very."
+        ),
+    ),
+    (
+        4,
+        "Persistent Transient Failure",
+        concat!(
+            "A persistent transient failure is one in which the",
+            " message as sent is valid, but persistence of some",
+            " temporary condition has caused abandonment or del",
+            "ay of attempts to send the message. If this code a",
+            "ccompanies a delivery failure report, sending in t",
+            "he future may be successful."
+        ),
+    ),
+    (
+        5,
+        "Permanent Failure",
+        concat!(
+            "A permanent failure is one which is not likely to ",
+            "be resolved by resending the message in the curren",
+            "t form. Some change to the message or the destinat",
+            "ion must be made for successful delivery."
+        ),
+    ),
+];
+
+/// Enhanced status code subjects: subject, title and description.
+pub static SUBJECT_CODES: &[(u8, &str, &str)] = &[
+    (
+        0,
+        "Other or Undefined Status",
+        concat!("There is no additional subject information availab", "le."),
+    ),
+    (
+        1,
+        "Addressing Status",
+        concat!(
+            "The address status reports on the originator or de",
+            "stination address. It may include address syntax o",
+            "r validity. These errors can generally be correcte",
+            "d by the sender and retried."
+        ),
+    ),
+    (
+        2,
+        "Mailbox Status",
+        concat!(
+            "Mailbox status indicates that something having to ",
+            "do with the mailbox has caused this DSN. Mailbox i",
+            "ssues are assumed to be under the general control ",
+            "of the recipient."
+        ),
+    ),
+    (
+        3,
+        "Mail System Status",
+        concat!(
+            "Mail system status indicates that something having",
+            " to do with the destination system has caused this",
+            " DSN. System issues are assumed to be under the ge",
+            "neral control of the destination system administra",
+            "tor."
+        ),
+    ),
+    (
+        4,
+        "Network and Routing Status",
+        concat!(
+            "The networking or routing codes report status abou",
+            "t the delivery system itself. These system compone",
+            "nts include any necessary infrastructure such as d",
+            "irectory and routing services. Network issues are ",
+            "assumed to be under the control of the destination",
+            " or intermediate system administrator."
+        ),
+    ),
+    (
+        5,
+        "Mail Delivery Protocol Status",
+        concat!(
+            "The mail delivery protocol status codes report fai",
+            "lures involving the message delivery protocol. The",
+            "se failures include the full range of problems res",
+            "ulting from implementation errors or an unreliable",
+            " connection."
+        ),
+    ),
+    (
+        6,
+        "Message Content or Media Status",
+        concat!(
+            "The message content or media status codes report f",
+            "ailures involving the content of the message. Thes",
+            "e codes report failures due to translation, transc",
+            "oding, or otherwise unsupported message media. Mes",
+            "sage content or media issues are under the control",
+            " of both the sender and the receiver, both of whic",
+            "h must support a common set of supported content-t",
+            "ypes."
+        ),
+    ),
+    (
+        7,
+        "Security or Policy Status",
+        concat!(
+            "The security or policy status codes report failure",
+            "s involving policies such as per-recipient or per-",
+            "host filtering and cryptographic operations. Secur",
+            "ity and policy status issues are assumed to be und",
+            "er the control of either or both the sender and re",
+            "cipient. Both the sender and recipient must permit",
+            " the exchange of messages and arrange the exchange",
+            " of necessary keys and certificates for cryptograp",
+            "hic operations."
+        ),
+    ),
+];
+
+/// Registered enhanced status codes, sorted by subject and detail.
+pub static STATUS_CODES: &[StatusCodeInfo] = &[
+    StatusCodeInfo {
+        subject: 0,
+        detail: 0,
+        title: "Other undefined Status",
+        description: concat!(
+            "Other undefined status is the only undefined error",
+            " code."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 1,
+        detail: 0,
+        title: "Other address status",
+        description: concat!(
+            "Something about the address specified in the messa",
+            "ge caused this DSN."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 1,
+        detail: 1,
+        title: "Bad destination mailbox address",
+        description: concat!(
+            "The mailbox specified in the address does not exis",
+            "t. For Internet mail names, this means the address",
+            " portion to the left of the \"@\" sign is invalid.",
+            " This code is only useful for permanent failures."
+        ),
+        reply_codes: &[451, 550],
+    },
+    StatusCodeInfo {
+        subject: 1,
+        detail: 2,
+        title: "Bad destination system address",
+        description: concat!(
+            "The destination system specified in the address do",
+            "es not exist or is incapable of accepting mail. Fo",
+            "r Internet mail names, this means the address port",
+            "ion to the right of the \"@\" is invalid for mail.",
+            " This code is only useful for permanent failures."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 1,
+        detail: 3,
+        title: "Bad destination mailbox address syntax",
+        description: concat!(
+            "The destination address was syntactically invalid.",
+            " This can apply to any field in the address. This ",
+            "code is only useful for permanent failures."
+        ),
+        reply_codes: &[501],
+    },
+    StatusCodeInfo {
+        subject: 1,
+        detail: 4,
+        title: "Destination mailbox address ambiguous",
+        description: concat!(
+            "The mailbox address as specified matches one or mo",
+            "re recipients on the destination system. This may ",
+            "result if a heuristic address mapping algorithm is",
+            " used to map the specified address to a local mail",
+            "box name."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 1,
+        detail: 5,
+        title: "Destination address valid",
+        description: concat!(
+            "This mailbox address as specified was valid. This ",
+            "status code should be used for positive delivery r",
+            "eports."
+        ),
+        reply_codes: &[250],
+    },
+    StatusCodeInfo {
+        subject: 1,
+        detail: 6,
+        title: "Destination mailbox has moved, No forwarding address",
+        description: concat!(
+            "The mailbox address provided was at one time valid",
+            ", but mail is no longer being accepted for that ad",
+            "dress. This code is only useful for permanent fail",
+            "ures."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 1,
+        detail: 7,
+        title: "Bad sender's mailbox address syntax",
+        description: concat!(
+            "The sender's address was syntactically invalid. Th",
+            "is can apply to any field in the address."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 1,
+        detail: 8,
+        title: "Bad sender's system address",
+        description: concat!(
+            "The sender's system specified in the address does ",
+            "not exist or is incapable of accepting return mail",
+            ". For domain names, this means the address portion",
+            " to the right of the \"@\" is invalid for mail."
+        ),
+        reply_codes: &[451, 501],
+    },
+    StatusCodeInfo {
+        subject: 1,
+        detail: 9,
+        title: "Message relayed to non-compliant mailer",
+        description: concat!(
+            "The mailbox address specified was valid, but the m",
+            "essage has been relayed to a system that does not ",
+            "speak this protocol; no further information can be",
+            " provided."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 1,
+        detail: 10,
+        title: "Recipient address has null MX",
+        description: concat!(
+            "This status code is returned when the associated a",
+            "ddress is marked as invalid using a null MX."
+        ),
+        reply_codes: &[556],
+    },
+    StatusCodeInfo {
+        subject: 2,
+        detail: 0,
+        title: "Other or undefined mailbox status",
+        description: concat!(
+            "The mailbox exists, but something about the destin",
+            "ation mailbox has caused the sending of this DSN."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 2,
+        detail: 1,
+        title: "Mailbox disabled, not accepting messages",
+        description: concat!(
+            "The mailbox exists, but is not accepting messages.",
+            " This may be a permanent error if the mailbox will",
+            " never be re-enabled or a transient error if the m",
+            "ailbox is only temporarily disabled."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 2,
+        detail: 2,
+        title: "Mailbox full",
+        description: concat!(
+            "The mailbox is full because the user has exceeded ",
+            "a per-mailbox administrative quota or physical cap",
+            "acity. The general semantics implies that the reci",
+            "pient can delete messages to make more space avail",
+            "able. This code should be used as a persistent tra",
+            "nsient failure."
+        ),
+        reply_codes: &[552],
+    },
+    StatusCodeInfo {
+        subject: 2,
+        detail: 3,
+        title: "Message length exceeds administrative limit",
+        description: concat!(
+            "A per-mailbox administrative message length limit ",
+            "has been exceeded. This status code should be used",
+            " when the per-mailbox message length limit is less",
+            " than the general system limit. This code should b",
+            "e used as a permanent failure."
+        ),
+        reply_codes: &[552],
+    },
+    StatusCodeInfo {
+        subject: 2,
+        detail: 4,
+        title: "Mailing list expansion problem",
+        description: concat!(
+            "The mailbox is a mailing list address and the mail",
+            "ing list was unable to be expanded. This code may ",
+            "represent a permanent failure or a persistent tran",
+            "sient failure."
+        ),
+        reply_codes: &[450, 452],
+    },
+    StatusCodeInfo {
+        subject: 3,
+        detail: 0,
+        title: "Other or undefined mail system status",
+        description: concat!(
+            "The destination system exists and normally accepts",
+            " mail, but something about the system has caused t",
+            "he generation of this DSN."
+        ),
+        reply_codes: &[221, 250, 421, 451, 550, 554],
+    },
+    StatusCodeInfo {
+        subject: 3,
+        detail: 1,
+        title: "Mail system full",
+        description: concat!(
+            "Mail system storage has been exceeded. The general",
+            " semantics imply that the individual recipient may",
+            " not be able to delete material to make room for a",
+            "dditional messages."
+        ),
+        reply_codes: &[452],
+    },
+    StatusCodeInfo {
+        subject: 3,
+        detail: 2,
+        title: "System not accepting network messages",
+        description: concat!(
+            "The host on which the mailbox is resident is not a",
+            "ccepting messages. Examples of such conditions inc",
+            "lude an imminent shutdown, excessive load, or syst",
+            "em maintenance."
+        ),
+        reply_codes: &[453],
+    },
+    StatusCodeInfo {
+        subject: 3,
+        detail: 3,
+        title: "System not capable of selected features",
+        description: concat!(
+            "Selected features specified for the message are no",
+            "t supported by the destination system. This can oc",
+            "cur in gateways when features from one domain cann",
+            "ot be mapped onto the supported feature in another",
+            "."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 3,
+        detail: 4,
+        title: "Message too big for system",
+        description: concat!(
+            "The message is larger than per-message size limit.",
+            " This limit may either be for physical or administ",
+            "rative reasons."
+        ),
+        reply_codes: &[552, 554],
+    },
+    StatusCodeInfo {
+        subject: 3,
+        detail: 5,
+        title: "System incorrectly configured",
+        description: concat!(
+            "The system is not configured in a manner that will",
+            " permit it to accept this message."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 3,
+        detail: 6,
+        title: "Requested priority was changed",
+        description: concat!(
+            "The message was accepted for relay/delivery, but t",
+            "he requested priority (possibly the implied defaul",
+            "t) was not honoured. The human readable text after",
+            " the status code contains the new priority, follow",
+            "ed by SP (space) and explanatory human readable te",
+            "xt."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 4,
+        detail: 0,
+        title: "Other or undefined network or routing status",
+        description: concat!(
+            "Something went wrong with the networking, but it i",
+            "s not clear what the problem is, or the problem ca",
+            "nnot be well expressed with any of the other provi",
+            "ded detail codes."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 4,
+        detail: 1,
+        title: "No answer from host",
+        description: concat!(
+            "The outbound connection attempt was not answered, ",
+            "because either the remote system was busy, or was ",
+            "unable to take a call."
+        ),
+        reply_codes: &[451],
+    },
+    StatusCodeInfo {
+        subject: 4,
+        detail: 2,
+        title: "Bad connection",
+        description: concat!(
+            "The outbound connection was established, but was u",
+            "nable to complete the message transaction, either ",
+            "because of time-out, or inadequate connection qual",
+            "ity."
+        ),
+        reply_codes: &[421],
+    },
+    StatusCodeInfo {
+        subject: 4,
+        detail: 3,
+        title: "Directory server failure",
+        description: concat!(
+            "The network system was unable to forward the messa",
+            "ge, because a directory server was unavailable. Th",
+            "e inability to connect to an Internet DNS server i",
+            "s one example of the directory server failure erro",
+            "r."
+        ),
+        reply_codes: &[451],
+    },
+    StatusCodeInfo {
+        subject: 4,
+        detail: 4,
+        title: "Unable to route",
+        description: concat!(
+            "The mail system was unable to determine the next h",
+            "op for the message because the necessary routing i",
+            "nformation was unavailable from the directory serv",
+            "er. A DNS lookup returning only an SOA (Start of A",
+            "dministration) record for a domain name is one exa",
+            "mple of the unable to route error."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 4,
+        detail: 5,
+        title: "Mail system congestion",
+        description: concat!(
+            "The mail system was unable to deliver the message ",
+            "because the mail system was congested."
+        ),
+        reply_codes: &[451],
+    },
+    StatusCodeInfo {
+        subject: 4,
+        detail: 6,
+        title: "Routing loop detected",
+        description: concat!(
+            "A routing loop caused the message to be forwarded ",
+            "too many times, either because of incorrect routin",
+            "g tables or a user- forwarding loop."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 4,
+        detail: 7,
+        title: "Delivery time expired",
+        description: concat!(
+            "The message was considered too old by the rejectin",
+            "g system, either because it remained on that host ",
+            "too long or because the time-to-live value specifi",
+            "ed by the sender of the message was exceeded. If p",
+            "ossible, the code for the actual problem found whe",
+            "n delivery was attempted should be returned rather",
+            " than this code."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 5,
+        detail: 0,
+        title: "Other or undefined protocol status",
+        description: concat!(
+            "Something was wrong with the protocol necessary to",
+            " deliver the message to the next hop and the probl",
+            "em cannot be well expressed with any of the other ",
+            "provided detail codes."
+        ),
+        reply_codes: &[
+            220, 250, 251, 252, 253, 451, 452, 454, 458, 459, 501, 502, 503, 554,
+        ],
+    },
+    StatusCodeInfo {
+        subject: 5,
+        detail: 1,
+        title: "Invalid command",
+        description: concat!(
+            "A mail transaction protocol command was issued whi",
+            "ch was either out of sequence or unsupported."
+        ),
+        reply_codes: &[430, 500, 503, 530, 550, 555],
+    },
+    StatusCodeInfo {
+        subject: 5,
+        detail: 2,
+        title: "Syntax error",
+        description: concat!(
+            "A mail transaction protocol command was issued whi",
+            "ch could not be interpreted, either because the sy",
+            "ntax was wrong or the command is unrecognized."
+        ),
+        reply_codes: &[500],
+    },
+    StatusCodeInfo {
+        subject: 5,
+        detail: 3,
+        title: "Too many recipients",
+        description: concat!(
+            "More recipients were specified for the message tha",
+            "n could have been delivered by the protocol. This ",
+            "error should normally result in the segmentation o",
+            "f the message into two, the remainder of the recip",
+            "ients to be delivered on a subsequent delivery att",
+            "empt. It is included in this list in the event tha",
+            "t such segmentation is not possible."
+        ),
+        reply_codes: &[451],
+    },
+    StatusCodeInfo {
+        subject: 5,
+        detail: 4,
+        title: "Invalid command arguments",
+        description: concat!(
+            "A valid mail transaction protocol command was issu",
+            "ed with invalid arguments, either because the argu",
+            "ments were out of range or represented unrecognize",
+            "d features."
+        ),
+        reply_codes: &[451, 501, 502, 503, 504, 550, 555],
+    },
+    StatusCodeInfo {
+        subject: 5,
+        detail: 5,
+        title: "Wrong protocol version",
+        description: concat!(
+            "A protocol version mis-match existed which could n",
+            "ot be automatically resolved by the communicating ",
+            "parties."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 5,
+        detail: 6,
+        title: "Authentication Exchange line is too long",
+        description: concat!(
+            "This enhanced status code SHOULD be returned when ",
+            "the server fails the AUTH command due to the clien",
+            "t sending a [BASE64] response which is longer than",
+            " the maximum buffer size available for the current",
+            "ly selected SASL mechanism."
+        ),
+        reply_codes: &[500],
+    },
+    StatusCodeInfo {
+        subject: 6,
+        detail: 0,
+        title: "Other or undefined media error",
+        description: concat!(
+            "Something about the content of a message caused it",
+            " to be considered undeliverable and the problem ca",
+            "nnot be well expressed with any of the other provi",
+            "ded detail codes."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 6,
+        detail: 1,
+        title: "Media not supported",
+        description: concat!(
+            "The media of the message is not supported by eithe",
+            "r the delivery protocol or the next system in the ",
+            "forwarding path."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 6,
+        detail: 2,
+        title: "Conversion required and prohibited",
+        description: concat!(
+            "The content of the message must be converted befor",
+            "e it can be delivered and such conversion is not p",
+            "ermitted. Such prohibitions may be the expression ",
+            "of the sender in the message itself or the policy ",
+            "of the sending host."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 6,
+        detail: 3,
+        title: "Conversion required but not supported",
+        description: concat!(
+            "The message content must be converted in order to ",
+            "be forwarded but such conversion is not possible o",
+            "r is not practical by a host in the forwarding pat",
+            "h. This condition may result when an ESMTP gateway",
+            " supports 8bit transport but is not able to downgr",
+            "ade the message to 7 bit as required for the next ",
+            "hop."
+        ),
+        reply_codes: &[554],
+    },
+    StatusCodeInfo {
+        subject: 6,
+        detail: 4,
+        title: "Conversion with loss performed",
+        description: concat!(
+            "This is a warning sent to the sender when message ",
+            "delivery was successfully but when the delivery re",
+            "quired a conversion in which some data was lost. T",
+            "his may also be a permanent error if the sender ha",
+            "s indicated that conversion with loss is prohibite",
+            "d for the message."
+        ),
+        reply_codes: &[250],
+    },
+    StatusCodeInfo {
+        subject: 6,
+        detail: 5,
+        title: "Conversion Failed",
+        description: concat!(
+            "A conversion was required but was unsuccessful. Th",
+            "is may be useful as a permanent or persistent temp",
+            "orary notification."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 6,
+        detail: 6,
+        title: "Message content not available",
+        description: concat!(
+            "The message content could not be fetched from a re",
+            "mote system. This may be useful as a permanent or ",
+            "persistent temporary notification."
+        ),
+        reply_codes: &[554],
+    },
+    StatusCodeInfo {
+        subject: 6,
+        detail: 7,
+        title: "Non-ASCII addresses not permitted for that sender/recipient",
+        description: concat!(
+            "This indicates the reception of a MAIL or RCPT com",
+            "mand that non-ASCII addresses are not permitted"
+        ),
+        reply_codes: &[550, 553],
+    },
+    StatusCodeInfo {
+        subject: 6,
+        detail: 8,
+        title: "UTF-8 string reply is required, but not permitted by the SMTP client",
+        description: concat!(
+            "This indicates that a reply containing a UTF-8 str",
+            "ing is required to show the mailbox name, but that",
+            " form of response is not permitted by the SMTP cli",
+            "ent."
+        ),
+        reply_codes: &[252, 550, 553],
+    },
+    StatusCodeInfo {
+        subject: 6,
+        detail: 9,
+        title: concat!(
+            "UTF-8 header message cannot be transferred to ",
+            "one or more recipients, so the message must be rejected"
+        ),
+        description: concat!(
+            "This indicates that transaction failed after the f",
+            "inal \".\" of the DATA command."
+        ),
+        reply_codes: &[550],
+    },
+    StatusCodeInfo {
+        subject: 6,
+        detail: 10,
+        title: "UTF-8 string reply is required, but not permitted by the SMTP client",
+        description: concat!(
+            "This indicates that a reply containing a UTF-8 str",
+            "ing is required to show the mailbox name, but that",
+            " form of response is not permitted by the SMTP cli",
+            "ent."
+        ),
+        reply_codes: &[252, 550, 553],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 0,
+        title: "Other or undefined security status",
+        description: concat!(
+            "Something related to security caused the message t",
+            "o be returned, and the problem cannot be well expr",
+            "essed with any of the other provided detail codes.",
+            " This status code may also be used when the condit",
+            "ion cannot be further described because of securit",
+            "y policies in force."
+        ),
+        reply_codes: &[220, 250, 450, 454, 500, 501, 503, 504, 530, 535, 550],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 1,
+        title: "Delivery not authorized, message refused",
+        description: concat!(
+            "The sender is not authorized to send to the destin",
+            "ation. This can be the result of per-host or per-r",
+            "ecipient filtering. This memo does not discuss the",
+            " merits of any such filtering, but provides a mech",
+            "anism to report such."
+        ),
+        reply_codes: &[451, 454, 502, 503, 533, 550, 551],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 2,
+        title: "Mailing list expansion prohibited",
+        description: concat!(
+            "The sender is not authorized to send a message to ",
+            "the intended mailing list."
+        ),
+        reply_codes: &[550],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 3,
+        title: "Security conversion required but not possible",
+        description: concat!(
+            "A conversion from one secure messaging protocol to",
+            " another was required for delivery and such conver",
+            "sion was not possible."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 4,
+        title: "Security features not supported",
+        description: concat!(
+            "A message contained security features such as secu",
+            "re authentication that could not be supported on t",
+            "he delivery protocol."
+        ),
+        reply_codes: &[504],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 5,
+        title: "Cryptographic failure",
+        description: concat!(
+            "A transport system otherwise authorized to validat",
+            "e or decrypt a message in transport was unable to ",
+            "do so because necessary information such as key wa",
+            "s not available or such information was invalid."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 6,
+        title: "Cryptographic algorithm not supported",
+        description: concat!(
+            "A transport system otherwise authorized to validat",
+            "e or decrypt a message was unable to do so because",
+            " the necessary algorithm was not supported."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 7,
+        title: "Message integrity failure",
+        description: concat!(
+            "A transport system otherwise authorized to validat",
+            "e a message was unable to do so because the messag",
+            "e was corrupted or altered. This may be useful as ",
+            "a permanent, transient persistent, or successful d",
+            "elivery code."
+        ),
+        reply_codes: &[],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 8,
+        title: "Authentication credentials invalid",
+        description: concat!(
+            "This response to the AUTH command indicates that t",
+            "he authentication failed due to invalid or insuffi",
+            "cient authentication credentials. In this case, th",
+            "e client SHOULD ask the user to supply new credent",
+            "ials (such as by presenting a password dialog box)",
+            "."
+        ),
+        reply_codes: &[535, 554],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 9,
+        title: "Authentication mechanism is too weak",
+        description: concat!(
+            "This response to the AUTH command indicates that t",
+            "he selected authentication mechanism is weaker tha",
+            "n server policy permits for that user. The client ",
+            "SHOULD retry with a new authentication mechanism."
+        ),
+        reply_codes: &[534],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 10,
+        title: "Encryption Needed",
+        description: concat!(
+            "This indicates that external strong privacy layer ",
+            "is needed in order to use the requested authentica",
+            "tion mechanism. This is primarily intended for use",
+            " with clear text authentication mechanisms. A clie",
+            "nt which receives this may activate a security lay",
+            "er such as TLS prior to authenticating, or attempt",
+            " to use a stronger mechanism."
+        ),
+        reply_codes: &[523],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 11,
+        title: "Encryption required for requested authentication mechanism",
+        description: concat!(
+            "This response to the AUTH command indicates that t",
+            "he selected authentication mechanism may only be u",
+            "sed when the underlying SMTP connection is encrypt",
+            "ed. Note that this response code is documented her",
+            "e for historical purposes only. Modern implementat",
+            "ions SHOULD NOT advertise mechanisms that are not ",
+            "permitted due to lack of encryption, unless an enc",
+            "ryption layer of sufficient strength is currently ",
+            "being employed."
+        ),
+        reply_codes: &[524, 538],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 12,
+        title: "A password transition is needed",
+        description: concat!(
+            "This response to the AUTH command indicates that t",
+            "he user needs to transition to the selected authen",
+            "tication mechanism. This is typically done by auth",
+            "enticating once using the [PLAIN] authentication m",
+            "echanism. The selected mechanism SHOULD then work ",
+            "for authentications in subsequent sessions."
+        ),
+        reply_codes: &[422],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 13,
+        title: "User Account Disabled",
+        description: concat!(
+            "Sometimes a system administrator will have to disa",
+            "ble a user's account (e.g., due to lack of payment",
+            ", abuse, evidence of a break-in attempt, etc). Thi",
+            "s error code occurs after a successful authenticat",
+            "ion to a disabled account. This informs the client",
+            " that the failure is permanent until the user cont",
+            "acts their system administrator to get the account",
+            " re-enabled. It differs from a generic authenticat",
+            "ion failure where the client's best option is to p",
+            "resent the passphrase entry dialog in case the use",
+            "r simply mistyped their passphrase."
+        ),
+        reply_codes: &[525],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 14,
+        title: "Trust relationship required",
+        description: concat!(
+            "The submission server requires a configured trust ",
+            "relationship with a third-party server in order to",
+            " access the message content. This value replaces t",
+            "he prior use of X.7.8 for this error condition. th",
+            "ereby updating [RFC4468]."
+        ),
+        reply_codes: &[535, 554],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 15,
+        title: "Priority Level is too low",
+        description: concat!(
+            "The specified priority level is below the lowest p",
+            "riority acceptable for the receiving SMTP server. ",
+            "This condition might be temporary, for example the",
+            " server is operating in a mode where only higher p",
+            "riority messages are accepted for transfer and del",
+            "ivery, while lower priority messages are rejected."
+        ),
+        reply_codes: &[450],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 16,
+        title: "Message is too big for the specified priority",
+        description: concat!(
+            "The message is too big for the specified priority.",
+            " This condition might be temporary, for example th",
+            "e server is operating in a mode where only higher ",
+            "priority messages below certain size are accepted ",
+            "for transfer and delivery."
+        ),
+        reply_codes: &[552, 554],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 17,
+        title: "Mailbox owner has changed",
+        description: concat!(
+            "This status code is returned when a message is rec",
+            "eived with a Require-Recipient-Valid-Since field o",
+            "r RRVS extension and the receiving system is able ",
+            "to determine that the intended recipient mailbox h",
+            "as not been under continuous ownership since the s",
+            "pecified date-time."
+        ),
+        reply_codes: &[550],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 18,
+        title: "Domain owner has changed",
+        description: concat!(
+            "This status code is returned when a message is rec",
+            "eived with a Require-Recipient-Valid-Since field o",
+            "r RRVS extension and the receiving system wishes t",
+            "o disclose that the owner of the domain name of th",
+            "e recipient has changed since the specified date-t",
+            "ime."
+        ),
+        reply_codes: &[550],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 19,
+        title: "RRVS test cannot be completed",
+        description: concat!(
+            "This status code is returned when a message is rec",
+            "eived with a Require-Recipient-Valid-Since field o",
+            "r RRVS extension and the receiving system cannot c",
+            "omplete the requested evaluation because the requi",
+            "red timestamp was not recorded. The message origin",
+            "ator needs to decide whether to reissue the messag",
+            "e without RRVS protection."
+        ),
+        reply_codes: &[550],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 20,
+        title: "No passing DKIM signature found",
+        description: concat!(
+            "This status code is returned when a message did no",
+            "t contain any passing DKIM signatures. (This viola",
+            "tes the advice of Section 6.1 of [RFC6376].)"
+        ),
+        reply_codes: &[550],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 21,
+        title: "No acceptable DKIM signature found",
+        description: concat!(
+            "This status code is returned when a message contai",
+            "ns one or more passing DKIM signatures, but none a",
+            "re acceptable. (This violates the advice of Sectio",
+            "n 6.1 of [RFC6376].)"
+        ),
+        reply_codes: &[550],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 22,
+        title: "No valid author-matched DKIM signature found",
+        description: concat!(
+            "This status code is returned when a message contai",
+            "ns one or more passing DKIM signatures, but none a",
+            "re acceptable because none have an identifier(s) t",
+            "hat matches the author address(es) found in the Fr",
+            "om header field. This is a special case of X.7.21.",
+            " (This violates the advice of Section 6.1 of [RFC6",
+            "376].)"
+        ),
+        reply_codes: &[550],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 23,
+        title: "SPF validation failed",
+        description: concat!(
+            "This status code is returned when a message comple",
+            "ted an SPF check that produced a \"fail\" result, ",
+            "contrary to local policy requirements. Used in pla",
+            "ce of 5.7.1 as described in Section 8.4 of [RFC720",
+            "8]."
+        ),
+        reply_codes: &[550],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 24,
+        title: "SPF validation error",
+        description: concat!(
+            "This status code is returned when evaluation of SP",
+            "F relative to an arriving message resulted in an e",
+            "rror. Used in place of 4.4.3 or 5.5.2 as described",
+            " in Sections 8.6 and 8.7 of [RFC7208]."
+        ),
+        reply_codes: &[451, 550],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 25,
+        title: "Reverse DNS validation failed",
+        description: concat!(
+            "This status code is returned when an SMTP client's",
+            " IP address failed a reverse DNS validation check,",
+            " contrary to local policy requirements."
+        ),
+        reply_codes: &[550],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 26,
+        title: "Multiple authentication checks failed",
+        description: concat!(
+            "This status code is returned when a message failed",
+            " more than one message authentication check, contr",
+            "ary to local policy requirements. The particular m",
+            "echanisms that failed are not specified."
+        ),
+        reply_codes: &[550],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 27,
+        title: "Sender address has null MX",
+        description: concat!(
+            "This status code is returned when the associated s",
+            "ender address has a null MX, and the SMTP receiver",
+            " is configured to reject mail from such sender (e.",
+            "g., because it could not return a DSN)."
+        ),
+        reply_codes: &[550],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 28,
+        title: "Mail flood detected",
+        description: concat!(
+            "The message appears to be part of a mail flood of ",
+            "similar abusive messages."
+        ),
+        reply_codes: &[421, 450, 451, 550, 554],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 29,
+        title: "ARC validation failure",
+        description: concat!(
+            "This status code may be returned when a message fa",
+            "ils ARC validation."
+        ),
+        reply_codes: &[550],
+    },
+    StatusCodeInfo {
+        subject: 7,
+        detail: 30,
+        title: "REQUIRETLS support required",
+        description: concat!(
+            "This indicates that the message was not able to be",
+            " forwarded because it was received with a REQUIRET",
+            "LS requirement and none of the SMTP servers to whi",
+            "ch the message should be forwarded provide this su",
+            "pport."
+        ),
+        reply_codes: &[550],
+    },
+];
+
+impl EnhancedStatusCode {
+    pub const fn new(class: u8, subject: u8, detail: u8) -> Self {
+        Self {
+            class,
+            subject,
+            detail,
+        }
+    }
+
+    /// Returns `true` if the class is 2 (success), 4 (persistent transient failure)
+    /// or 5 (permanent failure).
+    pub fn is_valid(&self) -> bool {
+        matches!(self.class, 2 | 4 | 5)
+    }
+
+    /// Returns `true` if the enhanced status code may be used with a basic reply
+    /// code, that is, the class of both codes is the same.
+    pub fn matches_reply_code(&self, code: u16) -> bool {
+        self.is_valid() && u16::from(self.class) == code / 100
+    }
+
+    /// Returns the registry entry for this code, if it is registered.
+    pub fn info(&self) -> Option<&'static StatusCodeInfo> {
+        STATUS_CODES
+            .binary_search_by_key(&(self.subject, self.detail), |info| {
+                (info.subject, info.detail)
+            })
+            .ok()
+            .map(|pos| &STATUS_CODES[pos])
+    }
+
+    /// Returns the title and description of the class.
+    pub fn explain_class(&self) -> Option<(&'static str, &'static str)> {
+        CLASS_CODES
+            .iter()
+            .find(|(class, _, _)| *class == self.class)
+            .map(|(_, title, description)| (*title, *description))
+    }
+
+    /// Returns the title and description of the subject.
+    pub fn explain_subject(&self) -> Option<(&'static str, &'static str)> {
+        SUBJECT_CODES
+            .iter()
+            .find(|(subject, _, _)| *subject == self.subject)
+            .map(|(_, title, description)| (*title, *description))
+    }
+
+    /// Looks up a registered status code by its title, ignoring case. The class is
+    /// taken from the `class` argument since the registry titles are shared by all classes.
+    pub fn from_title(class: u8, title: &str) -> Option<Self> {
+        STATUS_CODES
+            .iter()
+            .find(|info| info.title.eq_ignore_ascii_case(title))
+            .map(|info| Self::new(class, info.subject, info.detail))
+    }
+
+    /// Returns the registered status codes associated with a basic reply code.
+    pub fn for_reply_code(code: u16) -> impl Iterator<Item = Self> {
+        let class = (code / 100) as u8;
+        STATUS_CODES
+            .iter()
+            .filter(move |info| matches!(class, 2 | 4 | 5) && info.reply_codes.contains(&code))
+            .map(move |info| Self::new(class, info.subject, info.detail))
+    }
+}
+
+impl From<[u8; 3]> for EnhancedStatusCode {
+    fn from(code: [u8; 3]) -> Self {
+        Self::new(code[0], code[1], code[2])
+    }
+}
+
+impl From<EnhancedStatusCode> for [u8; 3] {
+    fn from(code: EnhancedStatusCode) -> Self {
+        [code.class, code.subject, code.detail]
+    }
+}
+
+impl FromStr for EnhancedStatusCode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('.');
+        let mut code = [0u8; 3];
+
+        for (pos, value) in code.iter_mut().enumerate() {
+            let part = parts.next().unwrap_or_default();
+            if (1..=if pos == 0 { 1 } else { 3 }).contains(&part.len())
+                && part.bytes().all(|ch| ch.is_ascii_digit())
+                && let Ok(part) = part.parse()
+            {
+                *value = part;
+            } else {
+                return Err(Error::SyntaxError {
+                    syntax: "class.subject.detail",
+                    context: ErrorContext::default(),
+                });
+            }
+        }
+
+        let code = EnhancedStatusCode::from(code);
+        if parts.next().is_none() && code.is_valid() {
+            Ok(code)
+        } else {
+            Err(Error::SyntaxError {
+                syntax: "class.subject.detail",
+                context: ErrorContext::default(),
+            })
+        }
+    }
+}
+
+impl Display for EnhancedStatusCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.class, self.subject, self.detail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{EnhancedStatusCode, Error, ErrorContext, Response};
+
+    use super::STATUS_CODES;
+
+    #[test]
+    fn parse_enhanced_status_code() {
+        for (code, expected) in [
+            ("2.0.0", Some(EnhancedStatusCode::new(2, 0, 0))),
+            ("5.7.30", Some(EnhancedStatusCode::new(5, 7, 30))),
+            ("4.4.7", Some(EnhancedStatusCode::new(4, 4, 7))),
+            ("5.1.100", Some(EnhancedStatusCode::new(5, 1, 100))),
+            ("3.0.0", None),
+            ("0.0.0", None),
+            ("55.1.1", None),
+            ("5.1", None),
+            ("5.1.1.1", None),
+            ("5..1", None),
+            ("5.1.1000", None),
+            ("5.1.256", None),
+            ("5.+1.1", None),
+            ("", None),
+        ] {
+            assert_eq!(
+                code.parse::<EnhancedStatusCode>().ok(),
+                expected,
+                "failed for {code:?}"
+            );
+            if let Some(expected) = expected {
+                assert_eq!(expected.to_string(), code);
+            }
+        }
+        assert_eq!(
+            "9.9.9".parse::<EnhancedStatusCode>(),
+            Err(Error::SyntaxError {
+                syntax: "class.subject.detail",
+                context: ErrorContext::default(),
+            })
+        );
+    }
+
+    #[test]
+    fn status_code_registry() {
+        assert!(
+            STATUS_CODES
+                .windows(2)
+                .all(|w| (w[0].subject, w[0].detail) < (w[1].subject, w[1].detail))
+        );
+
+        let code = EnhancedStatusCode::new(5, 7, 23);
+        assert!(code.matches_reply_code(550));
+        assert!(!code.matches_reply_code(450));
+        assert!(!EnhancedStatusCode::new(2, 0, 0).matches_reply_code(354));
+        let info = code.info().unwrap();
+        assert_eq!(info.title, "SPF validation failed");
+        assert_eq!(info.reply_codes, &[550]);
+        assert_eq!(
+            EnhancedStatusCode::from_title(5, "spf validation failed"),
+            Some(code)
+        );
+        assert_eq!(EnhancedStatusCode::from_title(5, "Unknown"), None);
+        assert_eq!(EnhancedStatusCode::new(4, 7, 31).info(), None);
+        assert_eq!(
+            EnhancedStatusCode::new(4, 7, 0).explain_class(),
+            Some(("Persistent Transient Failure", super::CLASS_CODES[1].2))
+        );
+
+        let codes = EnhancedStatusCode::for_reply_code(552).collect::<Vec<_>>();
+        for expected in [[5, 2, 2], [5, 2, 3], [5, 3, 4], [5, 7, 16]] {
+            assert!(codes.contains(&expected.into()), "missing {expected:?}");
+        }
+        assert!(codes.iter().all(|code| code.matches_reply_code(552)));
+
+        let response = Response::new(550, 5, 1, 10, "");
+        assert_eq!(
+            response.explain_status_code().unwrap().0,
+            "Recipient address has null MX"
+        );
+        assert_eq!(
+            response.explain_subject_code().unwrap().0,
+            "Addressing Status"
+        );
+        assert_eq!(Response::new_basic(550, "").explain_status_code(), None);
+    }
+}
@@ -0,0 +1,92 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs LLC <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: Apache-2.0 OR MIT
+ */
+
+// Helpers shared by the unit tests, the benchmarks and the fuzz target. They
+// only depend on `std`, so that the other crates can include this file with
+// `#[path]`.
+
+/// Returns a small xorshift generator, so that randomized tests are reproducible.
+pub fn xorshift(mut seed: u64) -> impl FnMut() -> u64 {
+    move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    }
+}
+
+/// Replaces bare CR and LF with CRLF and drops the final line break, which
+/// `DataReceiver` considers part of the terminator.
+pub fn normalize_line_breaks(body: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(body.len());
+    let mut last_cr = false;
+    for &ch in body {
+        match ch {
+            b'\n' if last_cr => (),
+            b'\r' | b'\n' => normalized.extend_from_slice(b"\r\n"),
+            _ => normalized.push(ch),
+        }
+        last_cr = ch == b'\r';
+    }
+    if normalized.ends_with(b"\r\n") {
+        normalized.truncate(normalized.len() - 2);
+    }
+    normalized
+}
+
+/// The byte-at-a-time `DataReceiver` that the memchr fast path replaced, used
+/// as a reference by the tests and as a baseline by the benchmarks. It is not
+/// the 0.2.1 receiver: it already un-stuffs a leading dot on the first line and
+/// ends an empty message, and it has none of the later size limit, bare line
+/// ending and statistics handling.
+pub struct PerByteDataReceiver {
+    crlf_dot: bool,
+    data_start: bool,
+    last_ch: u8,
+    prev_last_ch: u8,
+}
+
+impl Default for PerByteDataReceiver {
+    fn default() -> Self {
+        Self {
+            crlf_dot: false,
+            data_start: true,
+            last_ch: 0,
+            prev_last_ch: 0,
+        }
+    }
+}
+
+impl PerByteDataReceiver {
+    pub fn ingest(&mut self, bytes: &mut std::slice::Iter<'_, u8>, buf: &mut Vec<u8>) -> bool {
+        for &ch in bytes {
+            match ch {
+                b'.' if self.last_ch == b'\n' && self.prev_last_ch == b'\r'
+                    || self.data_start && !self.crlf_dot =>
+                {
+                    self.crlf_dot = true;
+                }
+                b'\n' if self.crlf_dot && self.last_ch == b'\r' => {
+                    buf.truncate(buf.len() - if self.data_start { 1 } else { 3 });
+                    return true;
+                }
+                b'\r' => {
+                    buf.push(ch);
+                    self.data_start &= self.crlf_dot;
+                }
+                _ => {
+                    buf.push(ch);
+                    self.crlf_dot = false;
+                    self.data_start = false;
+                }
+            }
+            self.prev_last_ch = self.last_ch;
+            self.last_ch = ch;
+        }
+
+        false
+    }
+}